@@ -0,0 +1,186 @@
+use std::io;
+use std::ops::{Deref, DerefMut};
+
+use crate::{ColorSpec, WriteColor};
+
+/// Extension methods for `WriteColor`.
+///
+/// This trait is implemented for every `WriteColor`, including trait objects.
+pub trait WriteColorExt: WriteColor {
+    /// Set the given color specification and return a guard that resets it
+    /// when dropped.
+    ///
+    /// The guard dereferences to this writer, so text can be written through
+    /// it. Since the writer is reset even when a `?` returns early, this
+    /// avoids leaving the terminal colored on errors.
+    ///
+    /// Calling `styled` on the guard itself nests a new guard, which restores
+    /// the outer guard's style instead of resetting when it is dropped. A
+    /// nested spec that doesn't reset is applied on top of the outer style.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use termcolor2::{ColorSpec, Recorder, WriteColorExt};
+    ///
+    /// let mut rec = Recorder::new();
+    /// {
+    ///     let mut red = rec.styled(&"fg:red".parse::<ColorSpec>()?)?;
+    ///     write!(red, "a")?;
+    ///     {
+    ///         let mut bold = red.styled(&"bold noreset".parse()?)?;
+    ///         write!(bold, "b")?;
+    ///     }
+    ///     write!(red, "c")?;
+    /// }
+    /// write!(rec, "d")?;
+    /// assert_eq!(rec.render(), "[fg:red]a[/][fg:red bold]b[/][fg:red]c[/]d");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn styled(
+        &mut self,
+        spec: &ColorSpec,
+    ) -> io::Result<StyleGuard<'_, Self>> {
+        StyleGuard::new(self, spec, None)
+    }
+}
+
+impl<W: WriteColor + ?Sized> WriteColorExt for W {}
+
+/// A guard that restores the style of a writer when dropped.
+///
+/// This is created by [`WriteColorExt::styled`]. A guard created directly on
+/// a writer resets it when dropped, while a guard created with
+/// [`StyleGuard::styled`] restores the style of the guard it was created
+/// from.
+///
+/// Errors that occur while restoring the style on drop are ignored. Use
+/// [`StyleGuard::finish`] to handle them.
+#[derive(Debug)]
+pub struct StyleGuard<'a, W: WriteColor + ?Sized> {
+    wtr: &'a mut W,
+    spec: ColorSpec,
+    outer: Option<ColorSpec>,
+    done: bool,
+}
+
+impl<'a, W: WriteColor + ?Sized> StyleGuard<'a, W> {
+    fn new(
+        wtr: &'a mut W,
+        spec: &ColorSpec,
+        outer: Option<ColorSpec>,
+    ) -> io::Result<StyleGuard<'a, W>> {
+        wtr.set_color(spec)?;
+        let spec = match outer {
+            Some(ref outer) => outer.apply(spec),
+            None => spec.clone(),
+        };
+        Ok(StyleGuard { wtr, spec, outer, done: false })
+    }
+
+    /// Set the given color specification and return a nested guard that
+    /// restores this guard's style when dropped.
+    ///
+    /// If the given spec doesn't reset, it is applied on top of this guard's
+    /// style.
+    pub fn styled(
+        &mut self,
+        spec: &ColorSpec,
+    ) -> io::Result<StyleGuard<'_, W>> {
+        StyleGuard::new(self.wtr, spec, Some(self.spec.clone()))
+    }
+
+    /// Returns the style set by this guard, including the style of any outer
+    /// guards it applies on top of.
+    pub fn spec(&self) -> &ColorSpec {
+        &self.spec
+    }
+
+    /// Restore the writer's style now and return any error that occurs.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.done = true;
+        self.restore()
+    }
+
+    fn restore(&mut self) -> io::Result<()> {
+        match self.outer {
+            None => self.wtr.reset(),
+            Some(ref outer) => {
+                if !outer.reset {
+                    self.wtr.reset()?;
+                }
+                self.wtr.set_color(outer)
+            }
+        }
+    }
+}
+
+impl<W: WriteColor + ?Sized> Deref for StyleGuard<'_, W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        self.wtr
+    }
+}
+
+impl<W: WriteColor + ?Sized> DerefMut for StyleGuard<'_, W> {
+    fn deref_mut(&mut self) -> &mut W {
+        self.wtr
+    }
+}
+
+impl<W: WriteColor + ?Sized> Drop for StyleGuard<'_, W> {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.restore();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+
+    use super::WriteColorExt;
+    use crate::tests::spec;
+    use crate::{Ansi, Recorder, WriteColor};
+
+    #[test]
+    fn resets_on_early_return() {
+        fn fails(wtr: &mut dyn WriteColor) -> io::Result<()> {
+            let mut wtr = wtr.styled(&spec("fg:red"))?;
+            write!(wtr, "a")?;
+            Err(io::Error::new(io::ErrorKind::InvalidData, "oops"))
+        }
+        let mut wtr = Ansi::new(vec![]);
+        assert!(fails(&mut wtr).is_err());
+        write!(wtr, "b").unwrap();
+        assert_eq!(wtr.into_inner(), b"\x1B[0;31ma\x1B[0mb");
+    }
+
+    #[test]
+    fn nesting() {
+        let mut rec = Recorder::new();
+        {
+            let mut outer = rec.styled(&spec("fg:red")).unwrap();
+            write!(outer, "a").unwrap();
+            {
+                let mut inner = outer.styled(&spec("bold noreset")).unwrap();
+                assert_eq!(inner.spec(), &spec("fg:red bold"));
+                write!(inner, "b").unwrap();
+                let mut innermost = inner.styled(&spec("fg:blue")).unwrap();
+                write!(innermost, "c").unwrap();
+                innermost.finish().unwrap();
+                write!(inner, "d").unwrap();
+            }
+            write!(outer, "e").unwrap();
+        }
+        write!(rec, "f").unwrap();
+        assert_eq!(
+            rec.render(),
+            "[fg:red]a[/][fg:red bold]b[/][fg:blue]c[/][fg:red bold]d[/]\
+             [fg:red]e[/]f"
+        );
+    }
+}
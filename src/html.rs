@@ -0,0 +1,552 @@
+use std::fmt::Write as _;
+use std::io;
+
+use crate::palette::system_index;
+use crate::{
+    Color, ColorSpec, HyperlinkSpec, Palette, UnderlineStyle, WriteColor,
+};
+
+/// The names of the 8 named colors, as used in CSS class names.
+const NAMES: [&str; 8] =
+    ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+/// The animation used for blinking text, which needs the `blink` keyframes
+/// from [`Html::stylesheet`].
+const BLINK_ANIMATION: &str = "animation: blink 1s step-end infinite";
+
+/// Satisfies `WriteColor` by writing HTML.
+///
+/// Text is escaped, and styled text is wrapped in `<span>` elements. By
+/// default, each span has an inline `style` attribute, with colors taken from
+/// a [`Palette`]. Alternatively, spans can use CSS classes, such as `fg-red`
+/// or `bold`, which can be styled with the stylesheet returned by
+/// [`Html::stylesheet`]. Hyperlinks are written as `<a href="...">`
+/// elements, but only for URIs with an `http`, `https`, `file` or `mailto`
+/// scheme. Since the text may come from other programs, e.g., through an
+/// [`AnsiReplay`](crate::AnsiReplay), links to other URIs, such as
+/// `javascript:` ones, are dropped and only their text is written. Blinking text uses a CSS animation in either case, whose
+/// keyframes are only defined in the stylesheet.
+///
+/// The markup is always well nested: a span never crosses the boundary of a
+/// hyperlink, and a new span is started whenever the style changes, whether
+/// or not the color specification resets. Call [`Html::finish`] once done to
+/// close any element that is still open.
+///
+/// The output doesn't contain any wrapping element. Since white space is
+/// significant, it is typically placed inside of a `<pre>` element.
+#[derive(Clone, Debug)]
+pub struct Html<W> {
+    wtr: W,
+    palette: Palette,
+    classes: bool,
+    /// The effective style of the text written now.
+    style: ColorSpec,
+    span_open: bool,
+    link_open: bool,
+}
+
+/// A color for a span, after applying reverse video.
+enum Paint {
+    /// One of the 16 system colors, which have their own CSS classes.
+    System(u8),
+    Rgb((u8, u8, u8)),
+}
+
+impl<W: io::Write> Html<W> {
+    /// Create a new HTML writer that uses inline styles with the default
+    /// palette.
+    pub fn new(wtr: W) -> Html<W> {
+        Html {
+            wtr,
+            palette: Palette::default(),
+            classes: false,
+            style: ColorSpec::new(),
+            span_open: false,
+            link_open: false,
+        }
+    }
+
+    /// Close any element that is still open, and return the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.close_span()?;
+        self.close_link()?;
+        Ok(self.wtr)
+    }
+
+    /// Return a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.wtr
+    }
+
+    /// Return a mutable reference to the inner writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.wtr
+    }
+
+    /// Return the palette used for colors.
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Set the palette used for colors.
+    ///
+    /// When CSS classes are used, the palette only applies to colors without
+    /// a class and to the stylesheet.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Returns true if spans use CSS classes instead of inline styles.
+    pub fn classes(&self) -> bool {
+        self.classes
+    }
+
+    /// Set whether spans use CSS classes instead of inline styles.
+    ///
+    /// With classes, the named colors and their intense variants use classes
+    /// such as `fg-red`, `bg-bright-red`, and attributes use classes such as
+    /// `bold` or `underline-curly`. All other colors still use inline styles.
+    pub fn set_classes(&mut self, yes: bool) {
+        self.classes = yes;
+    }
+
+    /// Returns a CSS stylesheet for the classes used when
+    /// [`Html::set_classes`] is enabled, based on this writer's palette.
+    pub fn stylesheet(&self) -> String {
+        let mut css = String::new();
+        for i in 0..16u8 {
+            let (r, g, b) = self.palette.get(i);
+            let name = system_class(i);
+            let _ = writeln!(
+                css,
+                ".fg-{name} {{ color: #{r:02x}{g:02x}{b:02x}; }}"
+            );
+            let _ = writeln!(
+                css,
+                ".bg-{name} {{ background-color: #{r:02x}{g:02x}{b:02x}; }}"
+            );
+        }
+        css.push_str(".bold { font-weight: bold; }\n");
+        css.push_str(".dimmed { opacity: 0.5; }\n");
+        css.push_str(".italic { font-style: italic; }\n");
+        css.push_str(".hidden { visibility: hidden; }\n");
+        let _ = writeln!(css, ".blink {{ {BLINK_ANIMATION}; }}");
+        css.push_str("@keyframes blink { 50% { opacity: 0; } }\n");
+        // Each line needs its own rule, since `text-decoration-line` set by
+        // one class would otherwise override the one set by another.
+        let lines = ["underline", "strikethrough", "overline"];
+        for mask in 1..8 {
+            let (mut selector, mut value) = (String::new(), vec![]);
+            for (i, class) in lines.iter().enumerate() {
+                if mask & (1 << i) != 0 {
+                    let _ = write!(selector, ".{class}");
+                    value.push(decoration_line(class));
+                }
+            }
+            let _ = writeln!(
+                css,
+                "{selector} {{ text-decoration-line: {}; }}",
+                value.join(" ")
+            );
+        }
+        for style in [
+            UnderlineStyle::Double,
+            UnderlineStyle::Curly,
+            UnderlineStyle::Dotted,
+            UnderlineStyle::Dashed,
+        ] {
+            let _ = writeln!(
+                css,
+                ".underline-{} {{ text-decoration-style: {}; }}",
+                style.name(),
+                decoration_style(style)
+            );
+        }
+        css
+    }
+
+    fn close_span(&mut self) -> io::Result<()> {
+        if self.span_open {
+            self.span_open = false;
+            self.wtr.write_all(b"</span>")?;
+        }
+        Ok(())
+    }
+
+    fn close_link(&mut self) -> io::Result<()> {
+        if self.link_open {
+            self.link_open = false;
+            self.wtr.write_all(b"</a>")?;
+        }
+        Ok(())
+    }
+
+    /// Open a span for the current style, if it has any.
+    fn open_span(&mut self) -> io::Result<()> {
+        if self.span_open || self.style.is_none() {
+            return Ok(());
+        }
+        let (classes, styles) = self.span_attributes();
+        let mut tag = String::from("<span");
+        if !classes.is_empty() {
+            let _ = write!(tag, " class=\"{}\"", classes.join(" "));
+        }
+        if !styles.is_empty() {
+            let _ = write!(tag, " style=\"{}\"", styles.join("; "));
+        }
+        tag.push('>');
+        self.span_open = true;
+        self.wtr.write_all(tag.as_bytes())
+    }
+
+    /// Returns the CSS classes and inline style declarations for the current
+    /// style.
+    fn span_attributes(&self) -> (Vec<String>, Vec<String>) {
+        let spec = &self.style;
+        let (mut classes, mut styles) = (vec![], vec![]);
+        // The default color is the same as no color.
+        let paint = |c: &Color| match system_index(c, spec.intense()) {
+            _ if *c == Color::Default => None,
+            Some(n) => Some(Paint::System(n)),
+            None => Some(Paint::Rgb(self.palette.resolve(c, spec.intense()))),
+        };
+        let (mut fg, mut bg) =
+            (spec.fg().and_then(paint), spec.bg().and_then(paint));
+        if spec.reverse() {
+            let old_fg = fg.unwrap_or(Paint::Rgb(self.palette.foreground()));
+            fg = Some(bg.unwrap_or(Paint::Rgb(self.palette.background())));
+            bg = Some(old_fg);
+        }
+        for (paint, prefix, property) in
+            [(fg, "fg", "color"), (bg, "bg", "background-color")]
+        {
+            match paint {
+                None => {}
+                Some(Paint::System(n)) if self.classes => {
+                    classes.push(format!("{}-{}", prefix, system_class(n)));
+                }
+                Some(Paint::System(n)) => {
+                    styles.push(css_color(property, self.palette.get(n)));
+                }
+                Some(Paint::Rgb(rgb)) => styles.push(css_color(property, rgb)),
+            }
+        }
+
+        let flags = [
+            (spec.bold(), "bold", "font-weight: bold"),
+            (spec.dimmed(), "dimmed", "opacity: 0.5"),
+            (spec.italic(), "italic", "font-style: italic"),
+            (spec.hidden(), "hidden", "visibility: hidden"),
+        ];
+        for (yes, class, style) in flags {
+            if !yes {
+                continue;
+            }
+            if self.classes {
+                classes.push(class.to_string());
+            } else {
+                styles.push(style.to_string());
+            }
+        }
+        if spec.blink() {
+            if self.classes {
+                classes.push("blink".to_string());
+            } else {
+                styles.push(BLINK_ANIMATION.to_string());
+            }
+        }
+
+        let lines = [
+            (spec.underline(), "underline"),
+            (spec.strikethrough(), "strikethrough"),
+            (spec.overline(), "overline"),
+        ];
+        let mut decoration = vec![];
+        for (yes, class) in lines {
+            if !yes {
+                continue;
+            }
+            if self.classes {
+                classes.push(class.to_string());
+            } else {
+                decoration.push(decoration_line(class));
+            }
+        }
+        if !decoration.is_empty() {
+            styles.push(format!(
+                "text-decoration-line: {}",
+                decoration.join(" ")
+            ));
+        }
+        if spec.underline() {
+            let style = spec.underline_style();
+            if style != UnderlineStyle::Single {
+                if self.classes {
+                    classes.push(format!("underline-{}", style.name()));
+                } else {
+                    styles.push(format!(
+                        "text-decoration-style: {}",
+                        decoration_style(style)
+                    ));
+                }
+            }
+            if let Some(c) = spec.underline_color() {
+                let rgb = self.palette.resolve(c, spec.intense());
+                styles.push(css_color("text-decoration-color", rgb));
+            }
+        }
+        (classes, styles)
+    }
+}
+
+/// Returns the name used in CSS classes for one of the 16 system colors.
+fn system_class(n: u8) -> String {
+    if n < 8 {
+        NAMES[n as usize].to_string()
+    } else {
+        format!("bright-{}", NAMES[(n - 8) as usize % 8])
+    }
+}
+
+fn css_color(property: &str, (r, g, b): (u8, u8, u8)) -> String {
+    format!("{property}: #{r:02x}{g:02x}{b:02x}")
+}
+
+fn decoration_line(class: &str) -> &str {
+    match class {
+        "strikethrough" => "line-through",
+        class => class,
+    }
+}
+
+fn decoration_style(style: UnderlineStyle) -> &'static str {
+    match style {
+        UnderlineStyle::Double => "double",
+        UnderlineStyle::Curly => "wavy",
+        UnderlineStyle::Dotted => "dotted",
+        UnderlineStyle::Dashed => "dashed",
+        _ => "solid",
+    }
+}
+
+/// Returns true if the given URI has a scheme that is safe to link to from
+/// HTML or SVG, i.e., one that can't run script.
+pub(crate) fn is_safe_link(uri: &[u8]) -> bool {
+    ["http:", "https:", "file:", "mailto:"].iter().any(|scheme| {
+        uri.get(..scheme.len())
+            .is_some_and(|s| s.eq_ignore_ascii_case(scheme.as_bytes()))
+    })
+}
+
+/// Write the given bytes with the characters that are special in HTML
+/// escaped.
+fn write_escaped<W: io::Write>(wtr: &mut W, buf: &[u8]) -> io::Result<()> {
+    let mut start = 0;
+    for (i, &b) in buf.iter().enumerate() {
+        let escaped: &[u8] = match b {
+            b'&' => b"&amp;",
+            b'<' => b"&lt;",
+            b'>' => b"&gt;",
+            b'"' => b"&quot;",
+            b'\'' => b"&#39;",
+            _ => continue,
+        };
+        wtr.write_all(&buf[start..i])?;
+        wtr.write_all(escaped)?;
+        start = i + 1;
+    }
+    wtr.write_all(&buf[start..])
+}
+
+impl<W: io::Write> io::Write for Html<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !buf.is_empty() {
+            self.open_span()?;
+            write_escaped(&mut self.wtr, buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+}
+
+impl<W: io::Write> WriteColor for Html<W> {
+    #[inline]
+    fn supports_color(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn supports_hyperlinks(&self) -> bool {
+        true
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        let next = self.style.apply(spec);
+        if next != self.style {
+            self.close_span()?;
+            self.style = next;
+        }
+        Ok(())
+    }
+
+    fn set_hyperlink(&mut self, link: &HyperlinkSpec) -> io::Result<()> {
+        self.close_span()?;
+        self.close_link()?;
+        if let Some(uri) = link.uri().filter(|uri| is_safe_link(uri)) {
+            self.wtr.write_all(b"<a href=\"")?;
+            write_escaped(&mut self.wtr, uri)?;
+            self.wtr.write_all(b"\">")?;
+            self.link_open = true;
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.close_span()?;
+        self.style.clear();
+        Ok(())
+    }
+
+    #[inline]
+    fn is_synchronous(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::Html;
+    use crate::tests::spec;
+    use crate::{Color, ColorSpec, HyperlinkSpec, Palette, WriteColor};
+
+    fn finish(wtr: Html<Vec<u8>>) -> String {
+        String::from_utf8(wtr.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn escaping() {
+        let mut wtr = Html::new(vec![]);
+        write!(wtr, "<a href=\"x\">&'</a>").unwrap();
+        assert_eq!(
+            finish(wtr),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn inline_styles() {
+        let mut wtr = Html::new(vec![]);
+        wtr.set_color(&spec("fg:red bold")).unwrap();
+        write!(wtr, "error").unwrap();
+        wtr.reset().unwrap();
+        write!(wtr, ": ").unwrap();
+        wtr.set_color(&spec("fg:rgb(1,2,3) bg:196 italic underline:curly"))
+            .unwrap();
+        write!(wtr, "x").unwrap();
+        assert_eq!(
+            finish(wtr),
+            "<span style=\"color: #cd0000; font-weight: bold\">error</span>: \
+             <span style=\"color: #010203; background-color: #ff0000; \
+             font-style: italic; text-decoration-line: underline; \
+             text-decoration-style: wavy\">x</span>"
+        );
+
+        let mut wtr = Html::new(vec![]);
+        wtr.set_color(&spec("blink strikethrough")).unwrap();
+        write!(wtr, "x").unwrap();
+        assert_eq!(
+            finish(wtr),
+            "<span style=\"animation: blink 1s step-end infinite; \
+             text-decoration-line: line-through\">x</span>"
+        );
+    }
+
+    #[test]
+    fn classes() {
+        let mut wtr = Html::new(vec![]);
+        wtr.set_classes(true);
+        let mut s = spec("fg:green bg:rgb(0,0,0) strikethrough underline");
+        s.set_intense(true);
+        wtr.set_color(&s).unwrap();
+        write!(wtr, "ok").unwrap();
+        assert_eq!(
+            finish(wtr),
+            "<span class=\"fg-bright-green underline strikethrough\" \
+             style=\"background-color: #000000\">ok</span>"
+        );
+
+        let css = Html::new(vec![]).stylesheet();
+        assert!(css.contains(".fg-bright-green { color: #00ff00; }"));
+        assert!(css.contains(
+            ".underline.strikethrough { text-decoration-line: underline \
+             line-through; }"
+        ));
+    }
+
+    #[test]
+    fn palette_and_reverse() {
+        let mut palette = Palette::new();
+        palette.set(4, (0x89, 0xB4, 0xFA)).set_background((1, 1, 1));
+        let mut wtr = Html::new(vec![]);
+        wtr.set_palette(palette);
+        wtr.set_color(&spec("fg:blue reverse")).unwrap();
+        write!(wtr, "x").unwrap();
+        assert_eq!(
+            finish(wtr),
+            "<span style=\"color: #010101; background-color: #89b4fa\">\
+             x</span>"
+        );
+    }
+
+    #[test]
+    fn well_nested() {
+        let mut wtr = Html::new(vec![]);
+        wtr.set_color(&spec("fg:red")).unwrap();
+        write!(wtr, "a").unwrap();
+        // Not resetting keeps the red foreground, in a new span.
+        wtr.set_color(&spec("bold noreset")).unwrap();
+        write!(wtr, "b").unwrap();
+        wtr.set_hyperlink(&HyperlinkSpec::open(b"https://x.y/?a&b")).unwrap();
+        write!(wtr, "c").unwrap();
+        wtr.set_color(&ColorSpec::new().set_fg(Some(Color::Blue)).clone())
+            .unwrap();
+        write!(wtr, "d").unwrap();
+        wtr.set_hyperlink(&HyperlinkSpec::close()).unwrap();
+        write!(wtr, "e").unwrap();
+        assert_eq!(
+            finish(wtr),
+            "<span style=\"color: #cd0000\">a</span>\
+             <span style=\"color: #cd0000; font-weight: bold\">b</span>\
+             <a href=\"https://x.y/?a&amp;b\">\
+             <span style=\"color: #cd0000; font-weight: bold\">c</span>\
+             <span style=\"color: #0000ee\">d</span></a>\
+             <span style=\"color: #0000ee\">e</span>"
+        );
+    }
+
+    #[test]
+    fn unsafe_links_are_dropped() {
+        let mut wtr = Html::new(vec![]);
+        for uri in [&b"javascript:alert(1)"[..], b" http://x", b"x/y"] {
+            wtr.set_hyperlink(&HyperlinkSpec::open(uri)).unwrap();
+            write!(wtr, "a").unwrap();
+            wtr.set_hyperlink(&HyperlinkSpec::close()).unwrap();
+        }
+        for uri in [&b"HTTPS://x"[..], b"file:///x", b"mailto:a@b"] {
+            wtr.set_hyperlink(&HyperlinkSpec::open(uri)).unwrap();
+            write!(wtr, "b").unwrap();
+        }
+        wtr.set_hyperlink(&HyperlinkSpec::close()).unwrap();
+        assert_eq!(
+            finish(wtr),
+            "aaa<a href=\"HTTPS://x\">b</a><a href=\"file:///x\">b</a>\
+             <a href=\"mailto:a@b\">b</a>"
+        );
+    }
+}
@@ -32,6 +32,39 @@ interleaving of buffer output.
 `io::Write`. These types are useful when you know exactly what you need. An
 analogous type for the Windows console is not provided since it cannot exist.
 
+`MinimalColor` wraps any `WriteColor` and only writes the difference between
+consecutive color specifications, which can greatly reduce the number of escape
+sequences written for heavily styled output.
+
+`StripAnsi` satisfies `WriteColor` for arbitrary implementors of `io::Write`
+like `NoColor`, but also removes any escape sequences already present in the
+text written to it, e.g., the output of a child process. The `strip_ansi`
+function does the same for a byte slice.
+
+`AnsiParser` goes the other way: it decodes text containing ANSI escape
+sequences, e.g., captured from another program, into text, `ColorSpec` and
+hyperlink events. `AnsiReplay` uses it to replay such text onto any
+`WriteColor`, so that it keeps its colors in a Windows console, or loses them
+cleanly when written to `NoColor`.
+
+`Html` satisfies `WriteColor` by writing HTML, with colors taken from a
+configurable `Palette`. `Svg` records styled text and renders it as an SVG
+image that looks like a terminal screenshot. `Recorder` records text along
+with its style, which makes it easy to test colored output. `Screen` goes
+further and shows what a terminal would display after carriage returns, cursor
+movements and overwrites are applied.
+
+`StyledString` is an owned sequence of styled spans of text, which can be
+built up front and then written to any `WriteColor`. The `write_styled!` and
+`writeln_styled!` macros write formatted text with inline style markup such as
+`{fg:red bold}error{/}`.
+
+The `WriteColorExt` trait adds a `styled` method to every `WriteColor`, which
+sets a style and returns a `StyleGuard` that resets it when dropped, even if
+an error causes an early return. `StyleStack` keeps a stack of nested styles,
+where each style pushed is layered over the current one and popping it
+restores the style around it.
+
 # Example: using `StandardStream`
 
 The `StandardStream` type in this crate works similarly to `std::io::Stdout`,
@@ -91,24 +124,39 @@ work around that, the recommended solution is to use the standard library's
 [`IsTerminal`](https://doc.rust-lang.org/std/io/trait.IsTerminal.html) trait.
 It goes out of its way to get it as right as possible in Windows environments.
 
-For example, in a command line application that exposes a `--color` flag,
-your logic for how to enable colors might look like this:
+`termcolor2` does this for you. When `StandardStream`,
+`BufferedStandardStream` or `BufferWriter` is given `ColorChoice::Auto`, colors
+are only enabled if the stream being written to (stdout or stderr) is a
+terminal. For example, in a command line application that exposes a `--color`
+flag, your logic for how to enable colors might look like this:
 
 ```ignore
-use std::io::IsTerminal;
-
 use termcolor2::{ColorChoice, StandardStream};
 
 let preference = argv.get_flag("color").unwrap_or("auto");
-let mut choice = preference.parse::<ColorChoice>()?;
-if choice == ColorChoice::Auto && !std::io::stdin().is_terminal() {
-    choice = ColorChoice::Never;
-}
+let choice = preference.parse::<ColorChoice>()?;
 let stdout = StandardStream::stdout(choice);
 // ... write to stdout
 ```
 
-Currently, `termcolor2` does not provide anything to do this for you.
+Similarly, not every terminal can display 24-bit colors. When writing ANSI
+escape sequences to stdout or stderr, `termcolor2` uses
+[`ColorLevel::detect`] to find out which colors the terminal supports, and
+maps `Rgb` and `Hex` colors to the nearest ones it can display. An `Ansi`
+writer can be given a [`ColorLevel`] explicitly via
+[`Ansi::with_color_level`].
+
+If you need to know why colors were enabled or disabled, for example to log
+it, then use [`ColorChoice::resolve`]:
+
+```rust
+use termcolor2::{ColorChoice, Stream};
+
+let resolution = ColorChoice::Auto.resolve(Stream::Stdout);
+if !resolution.enabled() {
+    eprintln!("colors disabled: {}", resolution.reason());
+}
+```
 */
 
 #![deny(missing_debug_implementations, missing_docs)]
@@ -120,19 +168,54 @@ Currently, `termcolor2` does not provide anything to do this for you.
 
 use std::env;
 use std::error;
+use std::ffi::OsString;
 use std::fmt;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 #[cfg(windows)]
 use std::sync::{Mutex, MutexGuard};
 
+use utils::hex_alpha;
+use utils::hex_to_rgb;
+use utils::parse_function;
 use utils::parse_hex;
 use utils::parse_other;
 use utils::parse_rgb;
+use utils::split_spec_tokens;
 #[cfg(windows)]
 use winapi_util::console as wincon;
 
+pub use guard::{StyleGuard, WriteColorExt};
+pub use html::Html;
+#[doc(hidden)]
+pub use markup::__WriteStyled;
+pub use minimal::MinimalColor;
+pub use palette::Palette;
+pub use parser::{AnsiEvent, AnsiParser};
+pub use recorder::{Recorder, Segment};
+pub use replay::AnsiReplay;
+pub use screen::{Cell, Screen};
+pub use stack::StyleStack;
+pub use strip::{strip_ansi, StripAnsi};
+pub use styled::StyledString;
+pub use svg::Svg;
+
+mod guard;
+mod html;
+mod markup;
+mod minimal;
+#[cfg(feature = "named-colors")]
+mod named;
+mod palette;
+mod parser;
+mod recorder;
+mod replay;
+mod screen;
+mod stack;
+mod strip;
+mod styled;
+mod svg;
 mod utils;
 
 /// This trait describes the behavior of writers that support colored output.
@@ -174,6 +257,36 @@ pub trait WriteColor: io::Write {
         false
     }
 
+    /// Returns true if and only if the underlying writer writes colors as
+    /// ANSI escape sequences, such that ANSI escape sequences written to it
+    /// directly via `io::Write` take effect in the same way. By default, this
+    /// always returns `false`.
+    ///
+    /// This is useful for writing generic code (such as an adapter that
+    /// minimizes the escape sequences written) that can take advantage of
+    /// escape sequences that can't be expressed with a `ColorSpec`.
+    fn is_ansi(&self) -> bool {
+        false
+    }
+
+    /// Used by `MinimalColor` to turn off attributes and colors, with SGR
+    /// parameters such as `22`, and set the changes in `delta` in a single
+    /// escape sequence. The result must be the same as setting `full`.
+    ///
+    /// This is not part of the public API. It can only be called from within
+    /// this crate, and only `Ansi` writers and the writers wrapping them
+    /// override it. By default, `full` is set.
+    #[doc(hidden)]
+    fn set_color_after_sgr(
+        &mut self,
+        _: minimal::Token,
+        _params: &[&str],
+        _delta: &ColorSpec,
+        full: &ColorSpec,
+    ) -> io::Result<()> {
+        self.set_color(full)
+    }
+
     /// Set the current hyperlink of the writer.
     ///
     /// The typical way to use this is to first call it with a
@@ -222,6 +335,18 @@ impl<T: ?Sized + WriteColor> WriteColor for &mut T {
     fn is_synchronous(&self) -> bool {
         (**self).is_synchronous()
     }
+    fn is_ansi(&self) -> bool {
+        (**self).is_ansi()
+    }
+    fn set_color_after_sgr(
+        &mut self,
+        token: minimal::Token,
+        params: &[&str],
+        delta: &ColorSpec,
+        full: &ColorSpec,
+    ) -> io::Result<()> {
+        (**self).set_color_after_sgr(token, params, delta, full)
+    }
 }
 
 impl<T: ?Sized + WriteColor> WriteColor for Box<T> {
@@ -243,6 +368,18 @@ impl<T: ?Sized + WriteColor> WriteColor for Box<T> {
     fn is_synchronous(&self) -> bool {
         (**self).is_synchronous()
     }
+    fn is_ansi(&self) -> bool {
+        (**self).is_ansi()
+    }
+    fn set_color_after_sgr(
+        &mut self,
+        token: minimal::Token,
+        params: &[&str],
+        delta: &ColorSpec,
+        full: &ColorSpec,
+    ) -> io::Result<()> {
+        (**self).set_color_after_sgr(token, params, delta, full)
+    }
 }
 
 /// ColorChoice represents the color preferences of an end user.
@@ -262,8 +399,9 @@ pub enum ColorChoice {
     /// than emitting ANSI color codes.
     AlwaysAnsi,
     /// Try to use colors, but don't force the issue. If the console isn't
-    /// available on Windows, or if TERM=dumb, or if `NO_COLOR` is defined, for
-    /// example, then don't use colors.
+    /// available on Windows, or if TERM=dumb, or if `NO_COLOR` is defined, or
+    /// if the stream being written to isn't a terminal, for example, then
+    /// don't use colors.
     Auto,
     /// Never emit colors.
     Never,
@@ -293,52 +431,134 @@ impl FromStr for ColorChoice {
 }
 
 impl ColorChoice {
+    /// Resolve this choice against the given standard stream and the current
+    /// environment.
+    ///
+    /// The resolution reports whether colors should be written and the reason
+    /// for that decision. This is the same logic used by `StandardStream`,
+    /// `BufferedStandardStream` and `BufferWriter`.
+    ///
+    /// Decisions are made in the following order, where the first rule that
+    /// applies wins:
+    ///
+    /// 1. Explicit choices (`Always`, `AlwaysAnsi` and `Never`) are returned
+    ///    as is. The rest only apply to `Auto`.
+    /// 2. If `NO_COLOR` is set, then colors are disabled.
+    /// 3. If `CLICOLOR_FORCE` is set to anything other than `0`, or if
+    ///    `FORCE_COLOR` is set to anything other than `0` or `false`, then
+    ///    colors are enabled, even if `stream` isn't a terminal.
+    /// 4. If `CLICOLOR=0`, then colors are disabled.
+    /// 5. If `TERM=dumb` (or, outside of Windows, if `TERM` isn't set), then
+    ///    colors are disabled.
+    /// 6. Colors are enabled if and only if `stream` is a terminal.
+    pub fn resolve(&self, stream: Stream) -> ColorResolution {
+        // Only bother checking the stream if the result depends on it.
+        let is_terminal = *self == ColorChoice::Auto && stream.is_terminal();
+        self.resolve_with(is_terminal, |key| env::var_os(key))
+    }
+
+    /// Resolve this choice like [`ColorChoice::resolve`], except whether the
+    /// stream is a terminal is given explicitly and environment variables are
+    /// looked up via the given function.
+    ///
+    /// This is useful for testing, or for resolving a choice against a
+    /// snapshot of some other process's environment.
+    pub fn resolve_with<F>(&self, is_terminal: bool, env: F) -> ColorResolution
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let reason = match *self {
+            ColorChoice::Always => ColorReason::Always,
+            ColorChoice::AlwaysAnsi => ColorReason::AlwaysAnsi,
+            ColorChoice::Never => ColorReason::Never,
+            ColorChoice::Auto => match self.env_decision(&env) {
+                Some(reason) => reason,
+                None if !is_terminal => ColorReason::NotTerminal,
+                None => ColorReason::Terminal,
+            },
+        };
+        ColorResolution { reason }
+    }
+
+    /// Returns this choice, except `Auto` becomes `Never` when colors should
+    /// not be written to the given stream.
+    fn for_stream(self, stream: Stream) -> ColorChoice {
+        if self == ColorChoice::Auto && !self.resolve(stream).enabled() {
+            ColorChoice::Never
+        } else {
+            self
+        }
+    }
+
     /// Returns true if we should attempt to write colored output.
     fn should_attempt_color(&self) -> bool {
         match *self {
             ColorChoice::Always => true,
             ColorChoice::AlwaysAnsi => true,
             ColorChoice::Never => false,
-            ColorChoice::Auto => self.env_allows_color(),
+            ColorChoice::Auto => {
+                match self.env_decision(&|key: &str| env::var_os(key)) {
+                    Some(reason) => reason.allows_color(),
+                    None => true,
+                }
+            }
+        }
+    }
+
+    /// Returns the decision made by the environment, if any. When this
+    /// returns `None`, the decision depends on whether the stream is a
+    /// terminal.
+    fn env_decision<F>(&self, env: &F) -> Option<ColorReason>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        if env("NO_COLOR").is_some() {
+            return Some(ColorReason::NoColor);
+        }
+        if let Some(v) = env("CLICOLOR_FORCE") {
+            if v != "0" {
+                return Some(ColorReason::CliColorForce);
+            }
+        }
+        if let Some(v) = env("FORCE_COLOR") {
+            if v != "0" && v != "false" {
+                return Some(ColorReason::ForceColor);
+            }
+        }
+        if let Some(v) = env("CLICOLOR") {
+            if v == "0" {
+                return Some(ColorReason::CliColorOff);
+            }
         }
+        self.env_term_decision(env)
     }
 
     #[cfg(not(windows))]
-    fn env_allows_color(&self) -> bool {
-        match env::var_os("TERM") {
+    fn env_term_decision<F>(&self, env: &F) -> Option<ColorReason>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        match env("TERM") {
             // If TERM isn't set, then we are in a weird environment that
             // probably doesn't support colors.
-            None => return false,
-            Some(k) => {
-                if k == "dumb" {
-                    return false;
-                }
-            }
+            None => Some(ColorReason::NoTerm),
+            Some(k) if k == "dumb" => Some(ColorReason::DumbTerm),
+            Some(_) => None,
         }
-        // If TERM != dumb, then the only way we don't allow colors at this
-        // point is if NO_COLOR is set.
-        if env::var_os("NO_COLOR").is_some() {
-            return false;
-        }
-        true
     }
 
     #[cfg(windows)]
-    fn env_allows_color(&self) -> bool {
+    fn env_term_decision<F>(&self, env: &F) -> Option<ColorReason>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
         // On Windows, if TERM isn't set, then we shouldn't automatically
         // assume that colors aren't allowed. This is unlike Unix environments
         // where TERM is more rigorously set.
-        if let Some(k) = env::var_os("TERM") {
-            if k == "dumb" {
-                return false;
-            }
-        }
-        // If TERM != dumb, then the only way we don't allow colors at this
-        // point is if NO_COLOR is set.
-        if env::var_os("NO_COLOR").is_some() {
-            return false;
+        match env("TERM") {
+            Some(k) if k == "dumb" => Some(ColorReason::DumbTerm),
+            _ => None,
         }
-        true
     }
 
     /// Returns true if this choice should forcefully use ANSI color codes.
@@ -364,6 +584,114 @@ impl ColorChoice {
     }
 }
 
+/// A standard stream that a `ColorChoice` can be resolved against.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Stream {
+    /// Standard output.
+    Stdout,
+    /// Standard error.
+    Stderr,
+}
+
+impl Stream {
+    /// Returns true if and only if this stream is connected to a terminal.
+    pub fn is_terminal(&self) -> bool {
+        match *self {
+            Stream::Stdout => io::stdout().is_terminal(),
+            Stream::Stderr => io::stderr().is_terminal(),
+        }
+    }
+}
+
+/// The outcome of resolving a `ColorChoice` via [`ColorChoice::resolve`].
+///
+/// This reports whether colors should be written and why.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ColorResolution {
+    reason: ColorReason,
+}
+
+impl ColorResolution {
+    /// Returns true if and only if colors should be written.
+    pub fn enabled(&self) -> bool {
+        self.reason.allows_color()
+    }
+
+    /// Returns the reason colors were enabled or disabled.
+    pub fn reason(&self) -> ColorReason {
+        self.reason
+    }
+}
+
+/// The reason a `ColorChoice` resolved the way it did.
+///
+/// The `Display` implementation for this type gives a short human readable
+/// explanation suitable for logging.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorReason {
+    /// The choice was `ColorChoice::Always`.
+    Always,
+    /// The choice was `ColorChoice::AlwaysAnsi`.
+    AlwaysAnsi,
+    /// The choice was `ColorChoice::Never`.
+    Never,
+    /// The choice was `ColorChoice::Auto` and the stream is a terminal.
+    Terminal,
+    /// The choice was `ColorChoice::Auto` and the stream isn't a terminal.
+    NotTerminal,
+    /// The choice was `ColorChoice::Auto` and `TERM` isn't set.
+    NoTerm,
+    /// The choice was `ColorChoice::Auto` and `TERM=dumb`.
+    DumbTerm,
+    /// The choice was `ColorChoice::Auto` and `NO_COLOR` is set.
+    NoColor,
+    /// The choice was `ColorChoice::Auto` and `CLICOLOR_FORCE` is set.
+    CliColorForce,
+    /// The choice was `ColorChoice::Auto` and `FORCE_COLOR` is set.
+    ForceColor,
+    /// The choice was `ColorChoice::Auto` and `CLICOLOR=0`.
+    CliColorOff,
+}
+
+impl ColorReason {
+    /// Returns true if this reason results in colors being written.
+    fn allows_color(&self) -> bool {
+        match *self {
+            ColorReason::Always
+            | ColorReason::AlwaysAnsi
+            | ColorReason::Terminal
+            | ColorReason::CliColorForce
+            | ColorReason::ForceColor => true,
+            ColorReason::Never
+            | ColorReason::NotTerminal
+            | ColorReason::NoTerm
+            | ColorReason::DumbTerm
+            | ColorReason::NoColor
+            | ColorReason::CliColorOff => false,
+        }
+    }
+}
+
+impl fmt::Display for ColorReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match *self {
+            ColorReason::Always => "color choice is 'always'",
+            ColorReason::AlwaysAnsi => "color choice is 'always-ansi'",
+            ColorReason::Never => "color choice is 'never'",
+            ColorReason::Terminal => "stream is a terminal",
+            ColorReason::NotTerminal => "stream is not a terminal",
+            ColorReason::NoTerm => "TERM is not set",
+            ColorReason::DumbTerm => "TERM is set to 'dumb'",
+            ColorReason::NoColor => "NO_COLOR is set",
+            ColorReason::CliColorForce => "CLICOLOR_FORCE is set",
+            ColorReason::ForceColor => "FORCE_COLOR is set",
+            ColorReason::CliColorOff => "CLICOLOR is set to '0'",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
 /// An error that occurs when parsing a `ColorChoice` fails.
 #[derive(Clone, Debug)]
 pub struct ColorChoiceParseError {
@@ -383,6 +711,74 @@ impl fmt::Display for ColorChoiceParseError {
     }
 }
 
+/// ColorLevel represents the range of colors a terminal is able to display.
+///
+/// When an `Ansi` writer is configured with a level below `TrueColor`, then
+/// colors it can't display are mapped to the nearest color it can. Namely,
+/// `Rgb` and `Hex` colors are mapped to the nearest xterm 256 color index,
+/// and with `Ansi16`, 256 color indices are further mapped to the nearest of
+/// the 16 system colors.
+///
+/// The `Default` implementation for this type selects `TrueColor`, which
+/// never changes any colors.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ColorLevel {
+    /// The 8 base colors and their 8 bright variants.
+    Ansi16,
+    /// The xterm 256 color palette.
+    Ansi256,
+    /// 24-bit RGB colors.
+    TrueColor,
+}
+
+/// The default is `TrueColor`.
+impl Default for ColorLevel {
+    fn default() -> ColorLevel {
+        ColorLevel::TrueColor
+    }
+}
+
+impl ColorLevel {
+    /// Detect the color level supported by the current terminal by inspecting
+    /// the environment.
+    ///
+    /// `COLORTERM=truecolor` (or `24bit`) selects `TrueColor`. Otherwise,
+    /// `TERM` is consulted: values ending in `-direct` select `TrueColor`,
+    /// values containing `256color` select `Ansi256` and any other value
+    /// selects `Ansi16`. A few terminals known to support 24-bit color are
+    /// also recognized via `TERM_PROGRAM` or `WT_SESSION`.
+    ///
+    /// If `TERM` isn't set at all, then nothing is known about the terminal
+    /// and `TrueColor` is returned so that colors aren't needlessly degraded.
+    pub fn detect() -> ColorLevel {
+        if let Some(v) = env::var_os("COLORTERM") {
+            if v == "truecolor" || v == "24bit" {
+                return ColorLevel::TrueColor;
+            }
+        }
+        if let Some(v) = env::var_os("TERM_PROGRAM") {
+            if v == "iTerm.app" || v == "WezTerm" || v == "vscode" {
+                return ColorLevel::TrueColor;
+            }
+        }
+        if env::var_os("WT_SESSION").is_some() {
+            return ColorLevel::TrueColor;
+        }
+        match env::var("TERM") {
+            Err(_) => ColorLevel::TrueColor,
+            Ok(term) => {
+                if term.ends_with("-direct") {
+                    ColorLevel::TrueColor
+                } else if term.contains("256color") {
+                    ColorLevel::Ansi256
+                } else {
+                    ColorLevel::Ansi16
+                }
+            }
+        }
+    }
+}
+
 /// `std::io` implements `Stdout` and `Stderr` (and their `Lock` variants) as
 /// separate types, which makes it difficult to abstract over them. We use
 /// some simple internal enum types to work around this.
@@ -401,6 +797,18 @@ enum IoStandardStream {
     StderrBuffered(io::BufWriter<io::Stderr>),
 }
 
+impl StandardStreamType {
+    /// Returns the standard stream that this type writes to.
+    fn stream(&self) -> Stream {
+        match *self {
+            StandardStreamType::Stdout
+            | StandardStreamType::StdoutBuffered => Stream::Stdout,
+            StandardStreamType::Stderr
+            | StandardStreamType::StderrBuffered => Stream::Stderr,
+        }
+    }
+}
+
 impl IoStandardStream {
     fn new(sty: StandardStreamType) -> IoStandardStream {
         match sty {
@@ -591,7 +999,7 @@ impl StandardStreamLock<'_> {
                 WriterInnerLock::NoColor(NoColor(w.0.lock()))
             }
             WriterInner::Ansi(ref w) => {
-                WriterInnerLock::Ansi(Ansi(w.0.lock()))
+                WriterInnerLock::Ansi(Ansi(w.0.lock(), w.1))
             }
         };
         StandardStreamLock { wtr: stream.wtr.wrap(locked) }
@@ -604,7 +1012,7 @@ impl StandardStreamLock<'_> {
                 WriterInnerLock::NoColor(NoColor(w.0.lock()))
             }
             WriterInner::Ansi(ref w) => {
-                WriterInnerLock::Ansi(Ansi(w.0.lock()))
+                WriterInnerLock::Ansi(Ansi(w.0.lock(), w.1))
             }
             #[cfg(windows)]
             WriterInner::Windows { ref wtr, ref console } => {
@@ -656,8 +1064,12 @@ impl WriterInner<IoStandardStream> {
         sty: StandardStreamType,
         choice: ColorChoice,
     ) -> WriterInner<IoStandardStream> {
+        let choice = choice.for_stream(sty.stream());
         if choice.should_attempt_color() {
-            WriterInner::Ansi(Ansi(IoStandardStream::new(sty)))
+            WriterInner::Ansi(Ansi(
+                IoStandardStream::new(sty),
+                AnsiCaps::detect(),
+            ))
         } else {
             WriterInner::NoColor(NoColor(IoStandardStream::new(sty)))
         }
@@ -673,6 +1085,7 @@ impl WriterInner<IoStandardStream> {
         sty: StandardStreamType,
         choice: ColorChoice,
     ) -> WriterInner<IoStandardStream> {
+        let choice = choice.for_stream(sty.stream());
         let mut con = match sty {
             StandardStreamType::Stdout => wincon::Console::stdout(),
             StandardStreamType::Stderr => wincon::Console::stderr(),
@@ -685,14 +1098,20 @@ impl WriterInner<IoStandardStream> {
             .unwrap_or(false);
         if choice.should_attempt_color() {
             if choice.should_ansi() || is_console_virtual {
-                WriterInner::Ansi(Ansi(IoStandardStream::new(sty)))
+                WriterInner::Ansi(Ansi(
+                    IoStandardStream::new(sty),
+                    AnsiCaps::detect(),
+                ))
             } else if let Ok(console) = con {
                 WriterInner::Windows {
                     wtr: IoStandardStream::new(sty),
                     console: Mutex::new(console),
                 }
             } else {
-                WriterInner::Ansi(Ansi(IoStandardStream::new(sty)))
+                WriterInner::Ansi(Ansi(
+                    IoStandardStream::new(sty),
+                    AnsiCaps::detect(),
+                ))
             }
         } else {
             WriterInner::NoColor(NoColor(IoStandardStream::new(sty)))
@@ -742,6 +1161,22 @@ impl WriteColor for StandardStream {
     fn is_synchronous(&self) -> bool {
         self.wtr.is_synchronous()
     }
+
+    #[inline]
+    fn is_ansi(&self) -> bool {
+        self.wtr.is_ansi()
+    }
+
+    #[inline]
+    fn set_color_after_sgr(
+        &mut self,
+        token: minimal::Token,
+        params: &[&str],
+        delta: &ColorSpec,
+        full: &ColorSpec,
+    ) -> io::Result<()> {
+        self.wtr.set_color_after_sgr(token, params, delta, full)
+    }
 }
 
 impl io::Write for StandardStreamLock<'_> {
@@ -786,6 +1221,22 @@ impl WriteColor for StandardStreamLock<'_> {
     fn is_synchronous(&self) -> bool {
         self.wtr.is_synchronous()
     }
+
+    #[inline]
+    fn is_ansi(&self) -> bool {
+        self.wtr.is_ansi()
+    }
+
+    #[inline]
+    fn set_color_after_sgr(
+        &mut self,
+        token: minimal::Token,
+        params: &[&str],
+        delta: &ColorSpec,
+        full: &ColorSpec,
+    ) -> io::Result<()> {
+        self.wtr.set_color_after_sgr(token, params, delta, full)
+    }
 }
 
 impl io::Write for BufferedStandardStream {
@@ -836,6 +1287,22 @@ impl WriteColor for BufferedStandardStream {
     fn is_synchronous(&self) -> bool {
         self.wtr.is_synchronous()
     }
+
+    #[inline]
+    fn is_ansi(&self) -> bool {
+        self.wtr.is_ansi()
+    }
+
+    #[inline]
+    fn set_color_after_sgr(
+        &mut self,
+        token: minimal::Token,
+        params: &[&str],
+        delta: &ColorSpec,
+        full: &ColorSpec,
+    ) -> io::Result<()> {
+        self.wtr.set_color_after_sgr(token, params, delta, full)
+    }
 }
 
 impl<W: io::Write> io::Write for WriterInner<W> {
@@ -922,6 +1389,30 @@ impl<W: io::Write> WriteColor for WriterInner<W> {
             WriterInner::Windows { .. } => true,
         }
     }
+
+    fn is_ansi(&self) -> bool {
+        match *self {
+            WriterInner::NoColor(_) => false,
+            WriterInner::Ansi(_) => true,
+            #[cfg(windows)]
+            WriterInner::Windows { .. } => false,
+        }
+    }
+
+    fn set_color_after_sgr(
+        &mut self,
+        token: minimal::Token,
+        params: &[&str],
+        delta: &ColorSpec,
+        full: &ColorSpec,
+    ) -> io::Result<()> {
+        match *self {
+            WriterInner::Ansi(ref mut wtr) => {
+                wtr.set_color_after_sgr(token, params, delta, full)
+            }
+            _ => self.set_color(full),
+        }
+    }
 }
 
 impl<W: io::Write> io::Write for WriterInnerLock<'_, W> {
@@ -1013,6 +1504,31 @@ impl<W: io::Write> WriteColor for WriterInnerLock<'_, W> {
             WriterInnerLock::Windows { .. } => true,
         }
     }
+
+    fn is_ansi(&self) -> bool {
+        match *self {
+            WriterInnerLock::Unreachable(_) => unreachable!(),
+            WriterInnerLock::NoColor(_) => false,
+            WriterInnerLock::Ansi(_) => true,
+            #[cfg(windows)]
+            WriterInnerLock::Windows { .. } => false,
+        }
+    }
+
+    fn set_color_after_sgr(
+        &mut self,
+        token: minimal::Token,
+        params: &[&str],
+        delta: &ColorSpec,
+        full: &ColorSpec,
+    ) -> io::Result<()> {
+        match *self {
+            WriterInnerLock::Ansi(ref mut wtr) => {
+                wtr.set_color_after_sgr(token, params, delta, full)
+            }
+            _ => self.set_color(full),
+        }
+    }
 }
 
 /// Writes colored buffers to stdout or stderr.
@@ -1030,6 +1546,7 @@ pub struct BufferWriter {
     printed: AtomicBool,
     separator: Option<Vec<u8>>,
     color_choice: ColorChoice,
+    ansi_caps: AnsiCaps,
     #[cfg(windows)]
     console: Option<Mutex<wincon::Console>>,
 }
@@ -1042,11 +1559,13 @@ impl BufferWriter {
     /// the buffers themselves.
     #[cfg(not(windows))]
     fn create(sty: StandardStreamType, choice: ColorChoice) -> BufferWriter {
+        let choice = choice.for_stream(sty.stream());
         BufferWriter {
             stream: LossyStandardStream::new(IoStandardStream::new(sty)),
             printed: AtomicBool::new(false),
             separator: None,
             color_choice: choice,
+            ansi_caps: AnsiCaps::detect(),
         }
     }
 
@@ -1060,6 +1579,7 @@ impl BufferWriter {
     /// the buffers themselves.
     #[cfg(windows)]
     fn create(sty: StandardStreamType, choice: ColorChoice) -> BufferWriter {
+        let choice = choice.for_stream(sty.stream());
         let mut con = match sty {
             StandardStreamType::Stdout => wincon::Console::stdout(),
             StandardStreamType::Stderr => wincon::Console::stderr(),
@@ -1082,6 +1602,7 @@ impl BufferWriter {
             printed: AtomicBool::new(false),
             separator: None,
             color_choice: choice,
+            ansi_caps: AnsiCaps::detect(),
             console: con.map(Mutex::new),
         }
     }
@@ -1124,7 +1645,7 @@ impl BufferWriter {
     /// be printed using the `print` method.
     #[cfg(not(windows))]
     pub fn buffer(&self) -> Buffer {
-        Buffer::new(self.color_choice)
+        Buffer::new(self.color_choice, self.ansi_caps)
     }
 
     /// Creates a new `Buffer` with the current color preferences.
@@ -1133,7 +1654,7 @@ impl BufferWriter {
     /// be printed using the `print` method.
     #[cfg(windows)]
     pub fn buffer(&self) -> Buffer {
-        Buffer::new(self.color_choice, self.console.is_some())
+        Buffer::new(self.color_choice, self.ansi_caps, self.console.is_some())
     }
 
     /// Prints the contents of the given buffer.
@@ -1204,9 +1725,9 @@ enum BufferInner {
 impl Buffer {
     /// Create a new buffer with the given color settings.
     #[cfg(not(windows))]
-    fn new(choice: ColorChoice) -> Buffer {
+    fn new(choice: ColorChoice, caps: AnsiCaps) -> Buffer {
         if choice.should_attempt_color() {
-            Buffer::ansi()
+            Buffer(BufferInner::Ansi(Ansi(vec![], caps)))
         } else {
             Buffer::no_color()
         }
@@ -1220,10 +1741,10 @@ impl Buffer {
     /// If coloring is desired and `console` is false, then ANSI escape
     /// sequences are used instead.
     #[cfg(windows)]
-    fn new(choice: ColorChoice, console: bool) -> Buffer {
+    fn new(choice: ColorChoice, caps: AnsiCaps, console: bool) -> Buffer {
         if choice.should_attempt_color() {
             if !console || choice.should_ansi() {
-                Buffer::ansi()
+                Buffer(BufferInner::Ansi(Ansi(vec![], caps)))
             } else {
                 Buffer::console()
             }
@@ -1239,7 +1760,13 @@ impl Buffer {
 
     /// Create a buffer that uses ANSI escape sequences.
     pub fn ansi() -> Buffer {
-        Buffer(BufferInner::Ansi(Ansi(vec![])))
+        Buffer::ansi_with_color_level(ColorLevel::TrueColor)
+    }
+
+    /// Create a buffer that uses ANSI escape sequences, and that only emits
+    /// colors supported by the given color level.
+    pub fn ansi_with_color_level(level: ColorLevel) -> Buffer {
+        Buffer(BufferInner::Ansi(Ansi::with_color_level(vec![], level)))
     }
 
     /// Create a buffer that can be written to a Windows console.
@@ -1384,6 +1911,32 @@ impl WriteColor for Buffer {
     fn is_synchronous(&self) -> bool {
         false
     }
+
+    #[inline]
+    fn is_ansi(&self) -> bool {
+        match self.0 {
+            BufferInner::NoColor(_) => false,
+            BufferInner::Ansi(_) => true,
+            #[cfg(windows)]
+            BufferInner::Windows(_) => false,
+        }
+    }
+
+    #[inline]
+    fn set_color_after_sgr(
+        &mut self,
+        token: minimal::Token,
+        params: &[&str],
+        delta: &ColorSpec,
+        full: &ColorSpec,
+    ) -> io::Result<()> {
+        match self.0 {
+            BufferInner::Ansi(ref mut w) => {
+                w.set_color_after_sgr(token, params, delta, full)
+            }
+            _ => self.set_color(full),
+        }
+    }
 }
 
 /// Satisfies `WriteColor` but ignores all color options.
@@ -1458,14 +2011,72 @@ impl<W: io::Write> WriteColor for NoColor<W> {
 }
 
 /// Satisfies `WriteColor` using standard ANSI escape sequences.
+///
+/// Colors are written as is unless a [`ColorLevel`] below `TrueColor` is
+/// set, in which case colors are mapped to the nearest ones that level can
+/// display.
 #[derive(Clone, Debug)]
-pub struct Ansi<W>(W);
+pub struct Ansi<W>(W, AnsiCaps);
+
+/// The terminal capabilities that an `Ansi` writer limits itself to.
+#[derive(Clone, Copy, Debug)]
+struct AnsiCaps {
+    level: ColorLevel,
+    underline_styles: bool,
+}
+
+impl AnsiCaps {
+    /// Capabilities that permit everything `Ansi` knows how to write.
+    fn all() -> AnsiCaps {
+        AnsiCaps { level: ColorLevel::TrueColor, underline_styles: true }
+    }
+
+    /// Capabilities detected from the environment.
+    fn detect() -> AnsiCaps {
+        AnsiCaps {
+            level: ColorLevel::detect(),
+            underline_styles: UnderlineStyle::detect_support(),
+        }
+    }
+}
 
 impl<W: Write> Ansi<W> {
     /// Create a new writer that satisfies `WriteColor` using standard ANSI
     /// escape sequences.
     pub fn new(wtr: W) -> Ansi<W> {
-        Ansi(wtr)
+        Ansi(wtr, AnsiCaps::all())
+    }
+
+    /// Create a new writer that satisfies `WriteColor` using standard ANSI
+    /// escape sequences, and that only emits colors supported by the given
+    /// color level.
+    pub fn with_color_level(wtr: W, level: ColorLevel) -> Ansi<W> {
+        Ansi(wtr, AnsiCaps { level, ..AnsiCaps::all() })
+    }
+
+    /// Return the color level that colors are mapped to.
+    pub fn color_level(&self) -> ColorLevel {
+        self.1.level
+    }
+
+    /// Set the color level that colors are mapped to.
+    pub fn set_color_level(&mut self, level: ColorLevel) {
+        self.1.level = level;
+    }
+
+    /// Return whether underline styles other than `UnderlineStyle::Single`
+    /// and underline colors are written.
+    pub fn underline_styles(&self) -> bool {
+        self.1.underline_styles
+    }
+
+    /// Set whether underline styles other than `UnderlineStyle::Single` and
+    /// underline colors are written.
+    ///
+    /// This is enabled by default. When disabled, styled underlines fall back
+    /// to a plain underline and underline colors are dropped.
+    pub fn set_underline_styles(&mut self, yes: bool) {
+        self.1.underline_styles = yes;
     }
 
     /// Consume this `Ansi` value and return the inner writer.
@@ -1517,31 +2128,21 @@ impl<W: io::Write> WriteColor for Ansi<W> {
 
     #[inline]
     fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
-        if spec.reset {
-            self.reset()?;
-        }
-        if spec.bold {
-            self.write_str("\x1B[1m")?;
-        }
-        if spec.dimmed {
-            self.write_str("\x1B[2m")?;
-        }
-        if spec.italic {
-            self.write_str("\x1B[3m")?;
-        }
-        if spec.underline {
-            self.write_str("\x1B[4m")?;
-        }
-        if spec.strikethrough {
-            self.write_str("\x1B[9m")?;
-        }
-        if let Some(ref c) = spec.fg_color {
-            self.write_color(true, c, spec.intense)?;
-        }
-        if let Some(ref c) = spec.bg_color {
-            self.write_color(false, c, spec.intense)?;
+        self.write_spec(&[], spec)
+    }
+
+    fn set_color_after_sgr(
+        &mut self,
+        _: minimal::Token,
+        params: &[&str],
+        delta: &ColorSpec,
+        full: &ColorSpec,
+    ) -> io::Result<()> {
+        let len: usize = params.iter().map(|p| p.len() + 1).sum();
+        if len > SgrBuffer::EXTRA {
+            return self.set_color(full);
         }
-        Ok(())
+        self.write_spec(params, delta)
     }
 
     #[inline]
@@ -1562,163 +2163,285 @@ impl<W: io::Write> WriteColor for Ansi<W> {
     fn is_synchronous(&self) -> bool {
         false
     }
+
+    #[inline]
+    fn is_ansi(&self) -> bool {
+        true
+    }
 }
 
-impl<W: io::Write> Ansi<W> {
-    fn write_str(&mut self, s: &str) -> io::Result<()> {
-        self.write_all(s.as_bytes())
+/// A stack allocated buffer for building a single SGR ("Select Graphic
+/// Rendition") escape sequence out of any number of parameters.
+///
+/// The largest sequence `Ansi` writes is a reset, ten attributes and three
+/// 24-bit colors (e.g., '38;2;255;255;255;' is 17 bytes), which comfortably
+/// fits in 96 bytes. The rest is left for parameters passed to
+/// `set_color_after_sgr`.
+struct SgrBuffer {
+    buf: [u8; 96 + SgrBuffer::EXTRA],
+    len: usize,
+}
+
+impl SgrBuffer {
+    /// The number of bytes available for parameters pushed before a
+    /// `ColorSpec`.
+    const EXTRA: usize = 48;
+
+    fn new() -> SgrBuffer {
+        let mut buf = [0u8; 96 + SgrBuffer::EXTRA];
+        buf[..2].copy_from_slice(b"\x1B[");
+        SgrBuffer { buf, len: 2 }
     }
 
-    fn write_color(
-        &mut self,
-        fg: bool,
-        c: &Color,
-        intense: bool,
-    ) -> io::Result<()> {
-        macro_rules! write_intense {
-            ($clr:expr) => {
-                if fg {
-                    self.write_str(concat!("\x1B[38;5;", $clr, "m"))
-                } else {
-                    self.write_str(concat!("\x1B[48;5;", $clr, "m"))
-                }
-            };
+    /// Returns true if no parameters have been pushed.
+    fn is_empty(&self) -> bool {
+        self.len == 2
+    }
+
+    /// Push a single numeric parameter.
+    fn push_code(&mut self, code: u8) {
+        let c1: u8 = (code / 100) % 10;
+        let c2: u8 = (code / 10) % 10;
+        let c3: u8 = code % 10;
+        let mut printed = false;
+
+        if c1 != 0 {
+            printed = true;
+            self.push_byte(b'0' + c1);
         }
-        macro_rules! write_normal {
-            ($clr:expr) => {
-                if fg {
-                    self.write_str(concat!("\x1B[3", $clr, "m"))
-                } else {
-                    self.write_str(concat!("\x1B[4", $clr, "m"))
-                }
-            };
+        if c2 != 0 || printed {
+            self.push_byte(b'0' + c2);
         }
-        macro_rules! write_var_ansi_code {
-            ($pre:expr, $($code:expr),+) => {{
-                // The loop generates at worst a literal of the form
-                // '255,255,255m' which is 12-bytes.
-                // The largest `pre` expression we currently use is 7 bytes.
-                // This gives us the maximum of 19-bytes for our work buffer.
-                let pre_len = $pre.len();
-                assert!(pre_len <= 7);
-                let mut fmt = [0u8; 19];
-                fmt[..pre_len].copy_from_slice($pre);
-                let mut i = pre_len - 1;
-                $(
-                    let c1: u8 = ($code / 100) % 10;
-                    let c2: u8 = ($code / 10) % 10;
-                    let c3: u8 = $code % 10;
-                    let mut printed = false;
-
-                    if c1 != 0 {
-                        printed = true;
-                        i += 1;
-                        fmt[i] = b'0' + c1;
-                    }
-                    if c2 != 0 || printed {
-                        i += 1;
-                        fmt[i] = b'0' + c2;
-                    }
-                    // If we received a zero value we must still print a value.
-                    i += 1;
-                    fmt[i] = b'0' + c3;
-                    i += 1;
-                    fmt[i] = b';';
-                )+
-
-                fmt[i] = b'm';
-                self.write_all(&fmt[0..i+1])
-            }}
-        }
-
-        macro_rules! write_string {
-            ($hex:expr) => {{
-                let hex = $hex.trim_start_matches('#');
-
-                if hex.len() == 3 {
-                    let r =
-                        u8::from_str_radix(&hex[0..1], 16).unwrap_or(0) * 17;
-                    let g =
-                        u8::from_str_radix(&hex[1..2], 16).unwrap_or(0) * 17;
-                    let b =
-                        u8::from_str_radix(&hex[2..3], 16).unwrap_or(0) * 17;
-
-                    if fg {
-                        write_var_ansi_code!(b"\x1B[38;2;", r, g, b)
-                    } else {
-                        write_var_ansi_code!(b"\x1B[48;2;", r, g, b)
-                    }
-                } else if hex.len() == 6 {
-                    // Full hex (e.g., #rrggbb)
-                    let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-                    let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-                    let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-
-                    if fg {
-                        write_var_ansi_code!(b"\x1B[38;2;", r, g, b)
-                    } else {
-                        write_var_ansi_code!(b"\x1B[48;2;", r, g, b)
-                    }
-                } else {
-                    let r = 0;
-                    let g = 0;
-                    let b = 0;
-                    if fg {
-                        write_var_ansi_code!(b"\x1B[38;2;", r, g, b)
-                    } else {
-                        write_var_ansi_code!(b"\x1B[48;2;", r, g, b)
-                    }
-                }
-            }};
+        // If we received a zero value we must still print a value.
+        self.push_byte(b'0' + c3);
+        self.push_byte(b';');
+    }
+
+    /// Push a parameter that isn't a single number, e.g., '4:3'.
+    fn push_str(&mut self, param: &str) {
+        for &b in param.as_bytes() {
+            self.push_byte(b);
         }
+        self.push_byte(b';');
+    }
 
-        macro_rules! write_custom {
-            ($ansi256:expr) => {
-                if fg {
-                    write_var_ansi_code!(b"\x1B[38;5;", $ansi256)
-                } else {
-                    write_var_ansi_code!(b"\x1B[48;5;", $ansi256)
+    #[inline]
+    fn push_byte(&mut self, b: u8) {
+        self.buf[self.len] = b;
+        self.len += 1;
+    }
+
+    /// Returns the escape sequence, terminated by 'm'. This must not be
+    /// called when no parameters have been pushed.
+    fn finish(&mut self) -> &[u8] {
+        // Replace the trailing ';' of the last parameter.
+        self.buf[self.len - 1] = b'm';
+        &self.buf[..self.len]
+    }
+}
+
+/// The form in which a color is written after being mapped to an `Ansi`
+/// writer's color level.
+enum SgrColor {
+    /// The terminal's default color, written as 'base+1', e.g., 39.
+    Default,
+    /// One of the 16 system colors, written with a single code.
+    Basic(u8),
+    /// An xterm 256 color index, written as 'base;5;n'.
+    Indexed(u8),
+    /// A 24-bit color, written as 'base;2;r;g;b'.
+    Rgb(u8, u8, u8),
+}
+
+impl<W: io::Write> Ansi<W> {
+    fn write_str(&mut self, s: &str) -> io::Result<()> {
+        self.write_all(s.as_bytes())
+    }
+
+    fn write_sgr(&mut self, mut sgr: SgrBuffer) -> io::Result<()> {
+        if sgr.is_empty() {
+            return Ok(());
+        }
+        self.write_all(sgr.finish())
+    }
+
+    /// Write the given SGR parameters followed by the ones for the given
+    /// color settings, as a single escape sequence.
+    fn write_spec(
+        &mut self,
+        params: &[&str],
+        spec: &ColorSpec,
+    ) -> io::Result<()> {
+        let mut sgr = SgrBuffer::new();
+        for param in params {
+            sgr.push_str(param);
+        }
+        if spec.reset {
+            sgr.push_code(0);
+        }
+        if spec.bold {
+            sgr.push_code(1);
+        }
+        if spec.dimmed {
+            sgr.push_code(2);
+        }
+        if spec.italic {
+            sgr.push_code(3);
+        }
+        if spec.underline {
+            match spec.underline_style {
+                UnderlineStyle::Double if self.1.underline_styles => {
+                    sgr.push_str("4:2")
                 }
-            };
+                UnderlineStyle::Curly if self.1.underline_styles => {
+                    sgr.push_str("4:3")
+                }
+                UnderlineStyle::Dotted if self.1.underline_styles => {
+                    sgr.push_str("4:4")
+                }
+                UnderlineStyle::Dashed if self.1.underline_styles => {
+                    sgr.push_str("4:5")
+                }
+                _ => sgr.push_code(4),
+            }
+        }
+        if spec.blink {
+            sgr.push_code(5);
+        }
+        if spec.reverse {
+            sgr.push_code(7);
+        }
+        if spec.hidden {
+            sgr.push_code(8);
+        }
+        if spec.strikethrough {
+            sgr.push_code(9);
+        }
+        if spec.overline {
+            sgr.push_code(53);
+        }
+        if let Some(ref c) = spec.fg_color {
+            self.push_color(&mut sgr, 38, c, spec.intense);
+        }
+        if let Some(ref c) = spec.bg_color {
+            self.push_color(&mut sgr, 48, c, spec.intense);
+        }
+        if let Some(ref c) = spec.underline_color {
+            self.push_underline_color(&mut sgr, c, spec.intense);
+        }
+        self.write_sgr(sgr)
+    }
 
-            ($r:expr, $g:expr, $b:expr) => {{
-                if fg {
-                    write_var_ansi_code!(b"\x1B[38;2;", $r, $g, $b)
-                } else {
-                    write_var_ansi_code!(b"\x1B[48;2;", $r, $g, $b)
+    /// Map the given color to the form it is written in at this writer's
+    /// color level.
+    fn sgr_color(&self, c: &Color, intense: bool) -> SgrColor {
+        if *c == Color::Default {
+            return SgrColor::Default;
+        }
+        // Bright colors are always written as one of the 16 system colors,
+        // i.e., as 90-97 (100-107).
+        if let Some(n) = palette::bright_index(c) {
+            return SgrColor::Basic(n);
+        }
+        let named = palette::named_index(c);
+        match self.1.level {
+            ColorLevel::Ansi16 => SgrColor::Basic(match (named, c.rgb()) {
+                (Some(n), _) if intense => n + 8,
+                (Some(n), _) => n,
+                (None, Some((r, g, b))) => palette::rgb_to_ansi16(r, g, b),
+                (None, None) => match *c {
+                    Color::Ansi256(n) => palette::ansi256_to_ansi16(n),
+                    _ => 0,
+                },
+            }),
+            level => match (named, c.rgb()) {
+                (Some(n), _) if intense => SgrColor::Basic(n + 8),
+                (Some(n), _) => SgrColor::Basic(n),
+                (None, Some((r, g, b))) if level == ColorLevel::Ansi256 => {
+                    SgrColor::Indexed(palette::rgb_to_ansi256(r, g, b))
                 }
-            }};
-        }
-        if intense {
-            match c {
-                Color::Black => write_intense!("8"),
-                Color::Blue => write_intense!("12"),
-                Color::Green => write_intense!("10"),
-                Color::Red => write_intense!("9"),
-                Color::Cyan => write_intense!("14"),
-                Color::Magenta => write_intense!("13"),
-                Color::Yellow => write_intense!("11"),
-                Color::White => write_intense!("15"),
-                Color::Ansi256(c) => write_custom!(c),
-                Color::Rgb(r, g, b) => write_custom!(r, g, b),
-                Color::Hex(hex) => write_string!(hex),
+                (None, Some((r, g, b))) => SgrColor::Rgb(r, g, b),
+                (None, None) => match *c {
+                    Color::Ansi256(n) => SgrColor::Indexed(n),
+                    _ => SgrColor::Basic(0),
+                },
+            },
+        }
+    }
+
+    /// Push the parameters for a foreground (`base` is 38) or background
+    /// (`base` is 48) color.
+    fn push_color(
+        &self,
+        sgr: &mut SgrBuffer,
+        base: u8,
+        c: &Color,
+        intense: bool,
+    ) {
+        match self.sgr_color(c, intense) {
+            SgrColor::Default => sgr.push_code(base + 1),
+            // The 8 normal colors are 30-37 (40-47), and the 8 bright colors
+            // are 90-97 (100-107).
+            SgrColor::Basic(n) if n < 8 => sgr.push_code(base - 8 + n),
+            SgrColor::Basic(n) => sgr.push_code(base + 52 + n - 8),
+            SgrColor::Indexed(n) => {
+                sgr.push_code(base);
+                sgr.push_code(5);
+                sgr.push_code(n);
             }
-        } else {
-            match c {
-                Color::Black => write_normal!("0"),
-                Color::Blue => write_normal!("4"),
-                Color::Green => write_normal!("2"),
-                Color::Red => write_normal!("1"),
-                Color::Cyan => write_normal!("6"),
-                Color::Magenta => write_normal!("5"),
-                Color::Yellow => write_normal!("3"),
-                Color::White => write_normal!("7"),
-                Color::Ansi256(c) => write_custom!(c),
-                Color::Rgb(r, g, b) => write_custom!(r, g, b),
-                Color::Hex(hex) => write_string!(hex),
+            SgrColor::Rgb(r, g, b) => {
+                sgr.push_code(base);
+                sgr.push_code(2);
+                sgr.push_code(r);
+                sgr.push_code(g);
+                sgr.push_code(b);
             }
         }
     }
+
+    /// Push the parameters for the underline color (SGR 58, or 59 for the
+    /// default color).
+    ///
+    /// Underline colors are only written if underline styles are enabled,
+    /// and never at `ColorLevel::Ansi16`, since terminals limited to 16
+    /// colors don't support them.
+    fn push_underline_color(
+        &self,
+        sgr: &mut SgrBuffer,
+        c: &Color,
+        intense: bool,
+    ) {
+        if !self.1.underline_styles || self.1.level == ColorLevel::Ansi16 {
+            return;
+        }
+        match self.sgr_color(c, intense) {
+            SgrColor::Default => sgr.push_code(59),
+            SgrColor::Basic(n) | SgrColor::Indexed(n) => {
+                sgr.push_code(58);
+                sgr.push_code(5);
+                sgr.push_code(n);
+            }
+            SgrColor::Rgb(r, g, b) => {
+                sgr.push_code(58);
+                sgr.push_code(2);
+                sgr.push_code(r);
+                sgr.push_code(g);
+                sgr.push_code(b);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn write_color(
+        &mut self,
+        fg: bool,
+        c: &Color,
+        intense: bool,
+    ) -> io::Result<()> {
+        let mut sgr = SgrBuffer::new();
+        self.push_color(&mut sgr, if fg { 38 } else { 48 }, c, intense);
+        self.write_sgr(sgr)
+    }
 }
 
 impl WriteColor for io::Sink {
@@ -1876,6 +2599,12 @@ pub struct ColorSpec {
     italic: bool,
     reset: bool,
     strikethrough: bool,
+    blink: bool,
+    reverse: bool,
+    hidden: bool,
+    overline: bool,
+    underline_style: UnderlineStyle,
+    underline_color: Option<Color>,
 }
 
 impl Default for ColorSpec {
@@ -1890,6 +2619,12 @@ impl Default for ColorSpec {
             italic: false,
             reset: true,
             strikethrough: false,
+            blink: false,
+            reverse: false,
+            hidden: false,
+            overline: false,
+            underline_style: UnderlineStyle::Single,
+            underline_color: None,
         }
     }
 }
@@ -1997,6 +2732,114 @@ impl ColorSpec {
         self
     }
 
+    /// Get the style of underline used when the text is underlined.
+    pub fn underline_style(&self) -> UnderlineStyle {
+        self.underline_style
+    }
+
+    /// Set the style of underline used when the text is underlined.
+    ///
+    /// This has no effect unless underlining is enabled via `set_underline`.
+    /// When writing ANSI escape sequences to a terminal that doesn't support
+    /// underline styles, a plain underline is used instead.
+    ///
+    /// Note that the underline style setting has no effect in a Windows
+    /// console.
+    pub fn set_underline_style(
+        &mut self,
+        style: UnderlineStyle,
+    ) -> &mut ColorSpec {
+        self.underline_style = style;
+        self
+    }
+
+    /// Get the underline color.
+    pub fn underline_color(&self) -> Option<&Color> {
+        self.underline_color.as_ref()
+    }
+
+    /// Set the underline color. When not set, underlines have the same color
+    /// as the text.
+    ///
+    /// When writing ANSI escape sequences to a terminal that doesn't support
+    /// underline colors, the underline color is ignored.
+    ///
+    /// Note that the underline color setting has no effect in a Windows
+    /// console.
+    pub fn set_underline_color(
+        &mut self,
+        color: Option<Color>,
+    ) -> &mut ColorSpec {
+        self.underline_color = color;
+        self
+    }
+
+    /// Get whether this is blinking or not.
+    ///
+    /// Note that the blink setting has no effect in a Windows console.
+    pub fn blink(&self) -> bool {
+        self.blink
+    }
+
+    /// Set whether the text is blinking or not.
+    ///
+    /// Many terminals don't support blinking text, or disable it by default.
+    ///
+    /// Note that the blink setting has no effect in a Windows console.
+    pub fn set_blink(&mut self, yes: bool) -> &mut ColorSpec {
+        self.blink = yes;
+        self
+    }
+
+    /// Get whether this is reversed or not.
+    ///
+    /// In a Windows console, reversing swaps the foreground and background
+    /// colors of this specification, and only has an effect if both are set.
+    pub fn reverse(&self) -> bool {
+        self.reverse
+    }
+
+    /// Set whether the foreground and background colors of the text are
+    /// swapped or not. This is also known as reverse video.
+    ///
+    /// In a Windows console, reversing swaps the foreground and background
+    /// colors of this specification, and only has an effect if both are set.
+    pub fn set_reverse(&mut self, yes: bool) -> &mut ColorSpec {
+        self.reverse = yes;
+        self
+    }
+
+    /// Get whether this is hidden or not.
+    ///
+    /// Note that the hidden setting has no effect in a Windows console.
+    pub fn hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Set whether the text is hidden or not. Hidden text still takes up
+    /// space, but isn't displayed.
+    ///
+    /// Note that the hidden setting has no effect in a Windows console.
+    pub fn set_hidden(&mut self, yes: bool) -> &mut ColorSpec {
+        self.hidden = yes;
+        self
+    }
+
+    /// Get whether this is overlined or not.
+    ///
+    /// Note that the overline setting has no effect in a Windows console.
+    pub fn overline(&self) -> bool {
+        self.overline
+    }
+
+    /// Set whether the text is overlined or not.
+    ///
+    /// Note that the overline setting has no effect in a Windows console.
+    pub fn set_overline(&mut self, yes: bool) -> &mut ColorSpec {
+        self.overline = yes;
+        self
+    }
+
     /// Get whether reset is enabled or not.
     ///
     /// reset is enabled by default. When disabled and using ANSI escape
@@ -2059,6 +2902,12 @@ impl ColorSpec {
             && !self.italic
             && !self.intense
             && !self.strikethrough
+            && !self.blink
+            && !self.reverse
+            && !self.hidden
+            && !self.overline
+            && self.underline_style == UnderlineStyle::Single
+            && self.underline_color.is_none()
     }
 
     /// Clears this color specification so that it has no color/style settings.
@@ -2071,18 +2920,108 @@ impl ColorSpec {
         self.dimmed = false;
         self.italic = false;
         self.strikethrough = false;
+        self.blink = false;
+        self.reverse = false;
+        self.hidden = false;
+        self.overline = false;
+        self.underline_style = UnderlineStyle::Single;
+        self.underline_color = None;
+    }
+
+    /// Returns the style that results from applying `over` on top of this
+    /// one, as an ANSI terminal would when `over` doesn't reset.
+    ///
+    /// That is, colors set in `over` replace the ones in this spec, while
+    /// colors that `over` leaves unset are inherited from this spec.
+    /// Attributes enabled in either spec are enabled in the result. The
+    /// result's reset setting is taken from `over`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use termcolor2::ColorSpec;
+    ///
+    /// let outer: ColorSpec = "fg:red bg:black bold".parse()?;
+    /// let inner: ColorSpec = "fg:blue italic".parse()?;
+    /// let merged: ColorSpec = "fg:blue bg:black bold italic".parse()?;
+    /// assert_eq!(outer.merged(&inner), merged);
+    /// # Ok::<(), termcolor2::ParseColorSpecError>(())
+    /// ```
+    pub fn merged(&self, over: &ColorSpec) -> ColorSpec {
+        let mut spec = self.clone();
+        if over.fg_color.is_some() {
+            spec.fg_color = over.fg_color.clone();
+        }
+        if over.bg_color.is_some() {
+            spec.bg_color = over.bg_color.clone();
+        }
+        if over.underline_color.is_some() {
+            spec.underline_color = over.underline_color.clone();
+        }
+        if over.underline {
+            spec.underline_style = over.underline_style;
+        }
+        spec.bold |= over.bold;
+        spec.intense |= over.intense;
+        spec.underline |= over.underline;
+        spec.dimmed |= over.dimmed;
+        spec.italic |= over.italic;
+        spec.strikethrough |= over.strikethrough;
+        spec.blink |= over.blink;
+        spec.reverse |= over.reverse;
+        spec.hidden |= over.hidden;
+        spec.overline |= over.overline;
+        spec.reset = over.reset;
+        spec
+    }
+
+    /// Returns the effective style after setting `spec` on a writer whose
+    /// current style is this one.
+    ///
+    /// A spec that resets replaces this style, while one that doesn't is
+    /// layered on top of it. The result always resets, so that setting it
+    /// on a writer reproduces the effective style from scratch.
+    pub(crate) fn apply(&self, spec: &ColorSpec) -> ColorSpec {
+        if spec.reset {
+            spec.clone()
+        } else {
+            self.layered(spec)
+        }
+    }
+
+    /// Returns the result of merging `over` on top of this style, as with
+    /// `merged`, but set to reset regardless of whether `over` resets.
+    pub(crate) fn layered(&self, over: &ColorSpec) -> ColorSpec {
+        let mut next = self.merged(over);
+        next.reset = true;
+        next
     }
 
     /// Writes this color spec to the given Windows console.
     #[cfg(windows)]
     fn write_console(&self, console: &mut wincon::Console) -> io::Result<()> {
-        let fg_color =
-            self.fg_color.clone().and_then(|c| c.to_windows(self.intense));
+        // The console has no notion of reverse video, so approximate it by
+        // swapping the colors we were given.
+        let (fg, bg) = if self.reverse
+            && self.fg_color.is_some()
+            && self.bg_color.is_some()
+        {
+            (&self.bg_color, &self.fg_color)
+        } else {
+            (&self.fg_color, &self.bg_color)
+        };
+        // The console can only restore its starting colors all at once, so
+        // do that first and then set the color that isn't the default.
+        if fg.as_ref() == Some(&Color::Default)
+            || bg.as_ref() == Some(&Color::Default)
+        {
+            console.reset()?;
+        }
+        let fg_color = fg.clone().and_then(|c| c.to_windows(self.intense));
         if let Some((intense, color)) = fg_color {
             console.fg(intense, color)?;
         }
-        let bg_color =
-            self.bg_color.clone().and_then(|c| c.to_windows(self.intense));
+        let bg_color = bg.clone().and_then(|c| c.to_windows(self.intense));
         if let Some((intense, color)) = bg_color {
             console.bg(intense, color)?;
         }
@@ -2090,8 +3029,326 @@ impl ColorSpec {
     }
 }
 
+/// The style of an underline.
+///
+/// Styles other than `Single` are written using the `4:x` escape sequences
+/// supported by many modern terminals. See [`UnderlineStyle::detect_support`].
+///
+/// The `FromStr` implementation for this type converts a lowercase string of
+/// the variant name to the corresponding variant.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum UnderlineStyle {
+    /// A single straight line.
+    Single,
+    /// Two straight lines.
+    Double,
+    /// A wavy line, as commonly used to mark spelling mistakes or errors.
+    Curly,
+    /// A dotted line.
+    Dotted,
+    /// A dashed line.
+    Dashed,
+}
+
+/// The default is `Single`.
+impl Default for UnderlineStyle {
+    fn default() -> UnderlineStyle {
+        UnderlineStyle::Single
+    }
+}
+
+impl UnderlineStyle {
+    /// Detect whether the current terminal supports underline styles and
+    /// underline colors by inspecting the environment.
+    ///
+    /// This recognizes terminals known to support them via `TERM`,
+    /// `TERM_PROGRAM`, `VTE_VERSION` and `WT_SESSION`. If support can't be
+    /// established, then this returns false.
+    pub fn detect_support() -> bool {
+        if let Ok(term) = env::var("TERM") {
+            let known = ["xterm-kitty", "xterm-ghostty", "wezterm", "foot"];
+            if known.iter().any(|k| term.starts_with(k)) {
+                return true;
+            }
+        }
+        if let Some(v) = env::var_os("TERM_PROGRAM") {
+            if v == "WezTerm" || v == "iTerm.app" || v == "ghostty" {
+                return true;
+            }
+        }
+        if let Ok(v) = env::var("VTE_VERSION") {
+            // VTE has supported them since 0.52.
+            if v.parse::<u32>().is_ok_and(|v| v >= 5200) {
+                return true;
+            }
+        }
+        env::var_os("WT_SESSION").is_some()
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            UnderlineStyle::Single => "single",
+            UnderlineStyle::Double => "double",
+            UnderlineStyle::Curly => "curly",
+            UnderlineStyle::Dotted => "dotted",
+            UnderlineStyle::Dashed => "dashed",
+        }
+    }
+}
+
+impl FromStr for UnderlineStyle {
+    type Err = ParseColorSpecError;
+
+    fn from_str(s: &str) -> Result<UnderlineStyle, ParseColorSpecError> {
+        match &*s.to_lowercase() {
+            "single" => Ok(UnderlineStyle::Single),
+            "double" => Ok(UnderlineStyle::Double),
+            "curly" => Ok(UnderlineStyle::Curly),
+            "dotted" => Ok(UnderlineStyle::Dotted),
+            "dashed" => Ok(UnderlineStyle::Dashed),
+            _ => Err(ParseColorSpecError {
+                kind: ParseColorSpecErrorKind::UnknownUnderlineStyle,
+                given: s.to_string(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for UnderlineStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Parses a color specification from a compact textual form.
+///
+/// A specification is a whitespace separated list of tokens, where each token
+/// is one of the following:
+///
+/// * `fg:COLOR`, `bg:COLOR` or `ul:COLOR`, which sets the foreground,
+///   background or underline color. `COLOR` may be anything accepted by
+///   `Color`'s `FromStr` implementation, but may not contain whitespace
+///   outside of parentheses.
+/// * `underline:STYLE`, which enables underlining with the given
+///   `UnderlineStyle`, e.g., `underline:curly`.
+/// * An attribute name, which enables that attribute: `bold`, `dimmed`,
+///   `italic`, `underline`, `blink`, `reverse`, `hidden`, `strikethrough`,
+///   `overline`, `intense` or `reset`.
+/// * An attribute name prefixed with `no` or `no-`, which disables that
+///   attribute, e.g., `nobold` or `no-reset`.
+///
+/// Later tokens override earlier ones. Tokens are matched case
+/// insensitively. For example, `fg:red bg:#222 bold underline`.
+///
+/// The `Display` implementation for `ColorSpec` writes this same format, and
+/// parsing its output yields an equivalent `ColorSpec`.
+impl FromStr for ColorSpec {
+    type Err = ParseColorSpecError;
+
+    fn from_str(s: &str) -> Result<ColorSpec, ParseColorSpecError> {
+        let mut spec = ColorSpec::new();
+        for token in split_spec_tokens(s) {
+            spec.apply_token(token)?;
+        }
+        Ok(spec)
+    }
+}
+
+impl fmt::Display for ColorSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tokens: Vec<String> = vec![];
+        if let Some(ref c) = self.fg_color {
+            tokens.push(format!("fg:{}", c));
+        }
+        if let Some(ref c) = self.bg_color {
+            tokens.push(format!("bg:{}", c));
+        }
+        if let Some(ref c) = self.underline_color {
+            tokens.push(format!("ul:{}", c));
+        }
+        for &(name, yes) in &self.attributes() {
+            if name == "underline"
+                && self.underline_style != Default::default()
+            {
+                tokens.push(format!("underline:{}", self.underline_style));
+                if !yes {
+                    tokens.push("nounderline".to_string());
+                }
+            } else if yes {
+                tokens.push(name.to_string());
+            }
+        }
+        if !self.reset {
+            tokens.push("noreset".to_string());
+        }
+        write!(f, "{}", tokens.join(" "))
+    }
+}
+
+impl ColorSpec {
+    /// Returns the name and value of each boolean style attribute, in the
+    /// order they are written by the `Display` implementation.
+    fn attributes(&self) -> [(&'static str, bool); 10] {
+        [
+            ("bold", self.bold),
+            ("dimmed", self.dimmed),
+            ("italic", self.italic),
+            ("underline", self.underline),
+            ("blink", self.blink),
+            ("reverse", self.reverse),
+            ("hidden", self.hidden),
+            ("strikethrough", self.strikethrough),
+            ("overline", self.overline),
+            ("intense", self.intense),
+        ]
+    }
+
+    /// Set the boolean attribute with the given name. Returns false if no
+    /// such attribute exists.
+    fn set_attribute(&mut self, name: &str, yes: bool) -> bool {
+        match name {
+            "bold" => self.set_bold(yes),
+            "dimmed" => self.set_dimmed(yes),
+            "italic" => self.set_italic(yes),
+            "underline" => self.set_underline(yes),
+            "blink" => self.set_blink(yes),
+            "reverse" => self.set_reverse(yes),
+            "hidden" => self.set_hidden(yes),
+            "strikethrough" => self.set_strikethrough(yes),
+            "overline" => self.set_overline(yes),
+            "intense" => self.set_intense(yes),
+            "reset" => self.set_reset(yes),
+            _ => return false,
+        };
+        true
+    }
+
+    /// Apply a single token of the textual color specification format.
+    fn apply_token(&mut self, token: &str) -> Result<(), ParseColorSpecError> {
+        let lower = token.to_ascii_lowercase();
+        if let Some(i) = token.find(':') {
+            let color = || {
+                token[i + 1..].parse::<Color>().map_err(|err| {
+                    ParseColorSpecError {
+                        kind: ParseColorSpecErrorKind::InvalidColor(err),
+                        given: token.to_string(),
+                    }
+                })
+            };
+            match &lower[..i] {
+                "fg" => {
+                    self.set_fg(Some(color()?));
+                }
+                "bg" => {
+                    self.set_bg(Some(color()?));
+                }
+                "ul" => {
+                    self.set_underline_color(Some(color()?));
+                }
+                "underline" => {
+                    let style = token[i + 1..].parse().map_err(|_| {
+                        ParseColorSpecError {
+                            kind:
+                                ParseColorSpecErrorKind::UnknownUnderlineStyle,
+                            given: token.to_string(),
+                        }
+                    })?;
+                    self.set_underline(true).set_underline_style(style);
+                }
+                _ => {
+                    return Err(ParseColorSpecError {
+                        kind: ParseColorSpecErrorKind::UnknownKey,
+                        given: token.to_string(),
+                    })
+                }
+            }
+            return Ok(());
+        }
+        if self.set_attribute(&lower, true) {
+            return Ok(());
+        }
+        if let Some(name) = lower.strip_prefix("no") {
+            let name = name.strip_prefix('-').unwrap_or(name);
+            if self.set_attribute(name, false) {
+                return Ok(());
+            }
+        }
+        Err(ParseColorSpecError {
+            kind: ParseColorSpecErrorKind::UnknownAttribute,
+            given: token.to_string(),
+        })
+    }
+}
+
+/// An error from parsing an invalid textual color specification.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseColorSpecError {
+    kind: ParseColorSpecErrorKind,
+    given: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ParseColorSpecErrorKind {
+    UnknownKey,
+    InvalidColor(ParseColorError),
+    UnknownAttribute,
+    UnknownUnderlineStyle,
+}
+
+impl ParseColorSpecError {
+    /// Return the token that couldn't be parsed.
+    pub fn invalid(&self) -> &str {
+        &self.given
+    }
+}
+
+impl error::Error for ParseColorSpecError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self.kind {
+            ParseColorSpecErrorKind::InvalidColor(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseColorSpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::ParseColorSpecErrorKind::*;
+        match self.kind {
+            UnknownKey => write!(
+                f,
+                "unrecognized key in '{}', should be 'fg', 'bg', 'ul' or \
+                 'underline'",
+                self.given
+            ),
+            UnknownUnderlineStyle => write!(
+                f,
+                "unrecognized underline style '{}'. Choose from: \
+                 single, double, curly, dotted, dashed",
+                self.given
+            ),
+            InvalidColor(ref err) => {
+                write!(f, "invalid color in '{}': {}", self.given, err)
+            }
+            UnknownAttribute => write!(
+                f,
+                "unrecognized style attribute '{}'. Choose from: \
+                 bold, dimmed, italic, underline, blink, reverse, hidden, \
+                 strikethrough, overline, intense, reset (optionally \
+                 prefixed with 'no')",
+                self.given
+            ),
+        }
+    }
+}
+
 /// The set of available colors for the terminal foreground/background.
 ///
+/// The eight bright colors, e.g., `BrightRed`, are written as the bright
+/// variants of the 16 system colors (SGR 90-97 and 100-107). Unlike the
+/// `intense` setting of a `ColorSpec`, they apply to one color only.
+///
 /// The `Ansi256` and `Rgb` colors will only output the correct codes when
 /// paired with the `Ansi` `WriteColor` implementation.
 ///
@@ -2105,12 +3362,36 @@ impl ColorSpec {
 /// readable form. The format is as follows:
 ///
 /// 1. Any of the explicitly listed colors in English. They are matched
-///    case insensitively.
+///    case insensitively. Bright colors are written as `bright-red`, and
+///    `brightred`, `bright red`, `light-red` and `light red` are also
+///    accepted. The terminal's default color is written as `default`.
 /// 2. A single 8-bit integer, in either decimal or hexadecimal format.
 /// 3. A triple of 8-bit integers separated by a comma, where each integer is
 ///    in decimal or hexadecimal format.
+/// 4. One of the 148 CSS named colors, such as `orange` or `rebeccapurple`,
+///    matched case insensitively and ignoring spaces and underscores. These
+///    are converted to `Rgb`, except for the eight colors listed above, which
+///    keep their meaning. Since spaces and underscores are ignored,
+///    `light green` is the CSS `lightgreen`, while `light-green` is the
+///    bright color. A name such as `light red`, which isn't a CSS color, is
+///    the bright color as well. This requires the `named-colors` feature,
+///    which is enabled by default.
+/// 5. A CSS Color Level 4 functional notation, i.e., `hsl()`, `hwb()`,
+///    `lab()`, `lch()`, `oklab()` or `oklch()`, or `hsv()`, which is written
+///    like `hsl()`. These are converted to `Rgb`, and colors outside of the
+///    sRGB gamut are brought into it by reducing their chroma. Colors with an
+///    alpha value below 1 are converted to a `Hex` color with an alpha
+///    component instead, e.g., `hsl(0 100% 50% / 50%)` is `#FF000080`.
 ///
 /// Hexadecimal numbers are written with a `0x` prefix.
+///
+/// `Hex` colors are written as `#RGB` or `#RRGGBB`, or with an alpha
+/// component as `#RGBA` or `#RRGGBBAA`. Terminals can't display transparent
+/// colors, so writers ignore the alpha component. Use [`Color::composite`]
+/// to blend such a color with a background color first.
+///
+/// The `Display` implementation for this type writes a color in a form that
+/// its `FromStr` implementation accepts.
 #[allow(missing_docs)]
 #[non_exhaustive]
 #[derive(Clone, Debug, PartialEq)]
@@ -2123,10 +3404,25 @@ pub enum Color {
     Magenta,
     Yellow,
     White,
+    BrightBlack,
+    BrightBlue,
+    BrightGreen,
+    BrightRed,
+    BrightCyan,
+    BrightMagenta,
+    BrightYellow,
+    BrightWhite,
     Ansi256(u8),
     Rgb(u8, u8, u8),
-    // Hex(String),
-    Hex(&'static str), // Use a reference with a 'static lifetime
+    Hex(String),
+    /// The terminal's default foreground or background color.
+    ///
+    /// This is written as SGR 39 (49), which switches back to the default
+    /// color without resetting any other attributes. On Windows consoles,
+    /// there is no way to restore only one of the console's starting colors,
+    /// so both are restored before the other color of the `ColorSpec`, if
+    /// any, is set again.
+    Default,
 }
 
 impl Eq for Color {}
@@ -2149,6 +3445,16 @@ impl Color {
             Color::Magenta => wincon::Color::Magenta,
             Color::Yellow => wincon::Color::Yellow,
             Color::White => wincon::Color::White,
+            Color::BrightBlack => return Some((Yes, wincon::Color::Black)),
+            Color::BrightBlue => return Some((Yes, wincon::Color::Blue)),
+            Color::BrightGreen => return Some((Yes, wincon::Color::Green)),
+            Color::BrightRed => return Some((Yes, wincon::Color::Red)),
+            Color::BrightCyan => return Some((Yes, wincon::Color::Cyan)),
+            Color::BrightMagenta => {
+                return Some((Yes, wincon::Color::Magenta))
+            }
+            Color::BrightYellow => return Some((Yes, wincon::Color::Yellow)),
+            Color::BrightWhite => return Some((Yes, wincon::Color::White)),
             Color::Ansi256(0) => return Some((No, wincon::Color::Black)),
             Color::Ansi256(1) => return Some((No, wincon::Color::Red)),
             Color::Ansi256(2) => return Some((No, wincon::Color::Green)),
@@ -2168,17 +3474,115 @@ impl Color {
             Color::Ansi256(_) => return None,
             Color::Rgb(_, _, _) => return None,
             Color::Hex(_) => return None,
+            Color::Default => return None,
         };
         let intense = if intense { Yes } else { No };
         Some((intense, color))
     }
 
+    /// Returns the alpha component of a `Hex` color written with one, e.g.,
+    /// `#FF000080`, where 255 is opaque. Other colors have no alpha
+    /// component.
+    pub fn alpha(&self) -> Option<u8> {
+        match *self {
+            Color::Hex(ref hex) => hex_alpha(hex),
+            _ => None,
+        }
+    }
+
+    /// Composites this color over the given background color, using the
+    /// default xterm values for colors that aren't RGB.
+    ///
+    /// This is the same as [`Palette::composite`] with the default palette.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use termcolor2::Color;
+    ///
+    /// let overlay: Color = "#FF000080".parse()?;
+    /// let bg = Color::Rgb(0, 0, 255);
+    /// assert_eq!(overlay.composite(&bg), Color::Rgb(128, 0, 127));
+    /// # Ok::<(), termcolor2::ParseColorError>(())
+    /// ```
+    pub fn composite(&self, background: &Color) -> Color {
+        Palette::default().composite(self, background)
+    }
+
+    /// Returns the red, green and blue components of an `Rgb` or `Hex` color.
+    fn rgb(&self) -> Option<(u8, u8, u8)> {
+        match *self {
+            Color::Rgb(r, g, b) => Some((r, g, b)),
+            Color::Hex(ref hex) => Some(hex_to_rgb(hex)),
+            _ => None,
+        }
+    }
+
+    /// Parses the name of a bright color, e.g., `bright-red`, `brightred` or
+    /// `light-red`.
+    ///
+    /// Names such as `light red`, where `light` is followed by a space or an
+    /// underscore, are handled by `Color::light` instead, since some of them
+    /// are CSS named colors.
+    fn bright(s: &str) -> Option<Color> {
+        let lower = s.to_ascii_lowercase();
+        match lower.strip_prefix("bright") {
+            Some(rest) => {
+                Color::bright_base(rest.trim_start_matches(['-', '_', ' ']))
+            }
+            None => Color::bright_base(lower.strip_prefix("light-")?),
+        }
+    }
+
+    /// Parses the name of a bright color written as `light` followed by a
+    /// space or an underscore, e.g., `light red`.
+    ///
+    /// This is only tried after CSS named colors, so that `light green` is
+    /// the CSS `lightgreen` while `light red`, which has no CSS equivalent,
+    /// is a bright color.
+    fn light(s: &str) -> Option<Color> {
+        let lower = s.to_ascii_lowercase();
+        let rest = lower.strip_prefix("light")?;
+        if !rest.starts_with(['_', ' ']) {
+            return None;
+        }
+        Color::bright_base(rest.trim_start_matches(['_', ' ']))
+    }
+
+    /// Returns the bright variant of one of the 8 named colors.
+    fn bright_base(base: &str) -> Option<Color> {
+        Some(match base {
+            "black" => Color::BrightBlack,
+            "blue" => Color::BrightBlue,
+            "green" => Color::BrightGreen,
+            "red" => Color::BrightRed,
+            "cyan" => Color::BrightCyan,
+            "magenta" => Color::BrightMagenta,
+            "yellow" => Color::BrightYellow,
+            "white" => Color::BrightWhite,
+            _ => return None,
+        })
+    }
+
+    /// Looks up a CSS named color.
+    #[cfg(feature = "named-colors")]
+    fn named(s: &str) -> Option<Color> {
+        named::lookup(s).map(|(r, g, b)| Color::Rgb(r, g, b))
+    }
+
+    #[cfg(not(feature = "named-colors"))]
+    fn named(_: &str) -> Option<Color> {
+        None
+    }
+
     /// Parses a numeric color string, either ANSI or RGB.
-    fn eval(s: &'static str) -> Result<Color, ParseColorError> {
+    fn eval(s: &str) -> Result<Color, ParseColorError> {
         if s.starts_with("#") {
             parse_hex(s)
         } else if s.starts_with("rgb(") {
             parse_rgb(s)
+        } else if s.contains('(') {
+            parse_function(s)
         } else {
             parse_other(s)
         }
@@ -2198,6 +3602,8 @@ enum ParseColorErrorKind {
     InvalidAnsi256,
     InvalidRgb,
     InvalidHex,
+    InvalidFunction,
+    ComponentOutOfRange { component: &'static str, range: &'static str },
 }
 
 impl ParseColorError {
@@ -2215,6 +3621,8 @@ impl error::Error for ParseColorError {
             InvalidAnsi256 => "invalid ansi256 color number",
             InvalidRgb => "invalid RGB color triple",
             InvalidHex => "invalid Hex string",
+            InvalidFunction => "invalid color function",
+            ComponentOutOfRange { .. } => "color component out of range",
         }
     }
 }
@@ -2223,6 +3631,22 @@ impl fmt::Display for ParseColorError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use self::ParseColorErrorKind::*;
         match self.kind {
+            #[cfg(feature = "named-colors")]
+            InvalidName => match named::closest(&self.given) {
+                Some(name) => write!(
+                    f,
+                    "unrecognized color name '{}', did you mean '{}'?",
+                    self.given, name
+                ),
+                None => write!(
+                    f,
+                    "unrecognized color name '{}'. Choose from: \
+                     black, blue, green, red, cyan, magenta, yellow, \
+                     white, or a CSS color name such as 'orange'",
+                    self.given
+                ),
+            },
+            #[cfg(not(feature = "named-colors"))]
             InvalidName => write!(
                 f,
                 "unrecognized color name '{}'. Choose from: \
@@ -2246,9 +3670,21 @@ impl fmt::Display for ParseColorError {
             InvalidHex => write!(
                 f,
                 "unrecognized Hex string, \
-                 should be '#RRGGBB', but is '{}'",
+                 should be '#RRGGBB' or '#RRGGBBAA', but is '{}'",
                 self.given
             ),
+            InvalidFunction => write!(
+                f,
+                "unrecognized color function, should be one of hsl(), \
+                 hsv(), hwb(), lab(), lch(), oklab() or oklch() with three \
+                 components and an optional alpha, but is '{}'",
+                self.given
+            ),
+            ComponentOutOfRange { component, range } => write!(
+                f,
+                "the {} of '{}' is out of range, should be {}",
+                component, self.given, range
+            ),
         }
     }
 }
@@ -2266,7 +3702,39 @@ impl FromStr for Color {
             "magenta" => Ok(Color::Magenta),
             "yellow" => Ok(Color::Yellow),
             "white" => Ok(Color::White),
-            _ => Color::eval(Box::leak(s.to_string().into_boxed_str())),
+            "default" => Ok(Color::Default),
+            _ => Color::bright(s)
+                .or_else(|| Color::named(s))
+                .or_else(|| Color::light(s))
+                .map_or_else(|| Color::eval(s), Ok),
+        }
+    }
+}
+
+/// Writes a color in a form that `Color`'s `FromStr` implementation accepts.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Color::Black => write!(f, "black"),
+            Color::Blue => write!(f, "blue"),
+            Color::Green => write!(f, "green"),
+            Color::Red => write!(f, "red"),
+            Color::Cyan => write!(f, "cyan"),
+            Color::Magenta => write!(f, "magenta"),
+            Color::Yellow => write!(f, "yellow"),
+            Color::White => write!(f, "white"),
+            Color::BrightBlack => write!(f, "bright-black"),
+            Color::BrightBlue => write!(f, "bright-blue"),
+            Color::BrightGreen => write!(f, "bright-green"),
+            Color::BrightRed => write!(f, "bright-red"),
+            Color::BrightCyan => write!(f, "bright-cyan"),
+            Color::BrightMagenta => write!(f, "bright-magenta"),
+            Color::BrightYellow => write!(f, "bright-yellow"),
+            Color::BrightWhite => write!(f, "bright-white"),
+            Color::Ansi256(n) => write!(f, "{}", n),
+            Color::Rgb(r, g, b) => write!(f, "rgb({},{},{})", r, g, b),
+            Color::Hex(ref hex) => write!(f, "{}", hex),
+            Color::Default => write!(f, "default"),
         }
     }
 }
@@ -2348,6 +3816,18 @@ impl<W: WriteColor> WriteColor for LossyStandardStream<W> {
     fn is_synchronous(&self) -> bool {
         self.wtr.is_synchronous()
     }
+    fn is_ansi(&self) -> bool {
+        self.wtr.is_ansi()
+    }
+    fn set_color_after_sgr(
+        &mut self,
+        token: minimal::Token,
+        params: &[&str],
+        delta: &ColorSpec,
+        full: &ColorSpec,
+    ) -> io::Result<()> {
+        self.wtr.set_color_after_sgr(token, params, delta, full)
+    }
 }
 
 impl<W: io::Write> io::Write for LossyStandardStream<W> {
@@ -2391,11 +3871,20 @@ fn write_lossy_utf8<W: io::Write>(mut w: W, buf: &[u8]) -> io::Result<usize> {
 
 #[cfg(test)]
 mod tests {
+    use std::ffi::OsString;
+
     use super::{
-        Ansi, Color, ColorSpec, HyperlinkSpec, ParseColorError,
-        ParseColorErrorKind, StandardStream, WriteColor,
+        Ansi, Color, ColorChoice, ColorLevel, ColorReason, ColorSpec,
+        HyperlinkSpec, ParseColorError, ParseColorErrorKind,
+        ParseColorSpecError, ParseColorSpecErrorKind, StandardStream,
+        UnderlineStyle, WriteColor,
     };
 
+    /// Parses a color specification, for use in the tests of all modules.
+    pub(crate) fn spec(s: &str) -> ColorSpec {
+        s.parse().unwrap()
+    }
+
     fn assert_is_send<T: Send>() {}
 
     #[test]
@@ -2403,6 +3892,80 @@ mod tests {
         assert_is_send::<StandardStream>();
     }
 
+    fn no_env(_: &str) -> Option<OsString> {
+        None
+    }
+
+    fn fake_env(
+        vars: &'static [(&'static str, &'static str)],
+    ) -> impl Fn(&str) -> Option<OsString> {
+        move |key| {
+            vars.iter().find(|&&(k, _)| k == key).map(|&(_, v)| v.into())
+        }
+    }
+
+    #[test]
+    fn test_resolve_explicit_choice() {
+        let res = ColorChoice::Always.resolve_with(false, no_env);
+        assert!(res.enabled());
+        assert_eq!(res.reason(), ColorReason::Always);
+
+        let res = ColorChoice::AlwaysAnsi.resolve_with(false, no_env);
+        assert!(res.enabled());
+        assert_eq!(res.reason(), ColorReason::AlwaysAnsi);
+
+        let res = ColorChoice::Never.resolve_with(true, no_env);
+        assert!(!res.enabled());
+        assert_eq!(res.reason(), ColorReason::Never);
+        assert_eq!(res.reason().to_string(), "color choice is 'never'");
+
+        // Explicit choices ignore the environment entirely.
+        let env = fake_env(&[("NO_COLOR", "1")]);
+        assert!(ColorChoice::Always.resolve_with(false, env).enabled());
+        let env = fake_env(&[("FORCE_COLOR", "1")]);
+        assert!(!ColorChoice::Never.resolve_with(true, env).enabled());
+    }
+
+    #[test]
+    fn test_resolve_auto_env() {
+        let auto = |is_terminal, vars| {
+            ColorChoice::Auto.resolve_with(is_terminal, fake_env(vars))
+        };
+
+        let res = auto(true, &[("TERM", "xterm")]);
+        assert_eq!(res.reason(), ColorReason::Terminal);
+        assert!(res.enabled());
+        let res = auto(false, &[("TERM", "xterm")]);
+        assert_eq!(res.reason(), ColorReason::NotTerminal);
+        assert!(!res.enabled());
+        let res = auto(true, &[("TERM", "dumb")]);
+        assert_eq!(res.reason(), ColorReason::DumbTerm);
+
+        let res = auto(false, &[("CLICOLOR_FORCE", "1")]);
+        assert_eq!(res.reason(), ColorReason::CliColorForce);
+        assert!(res.enabled());
+        let res = auto(false, &[("TERM", "dumb"), ("FORCE_COLOR", "")]);
+        assert_eq!(res.reason(), ColorReason::ForceColor);
+        assert!(res.enabled());
+        let res = auto(false, &[("TERM", "xterm"), ("FORCE_COLOR", "0")]);
+        assert_eq!(res.reason(), ColorReason::NotTerminal);
+        let res = auto(false, &[("TERM", "xterm"), ("CLICOLOR_FORCE", "0")]);
+        assert_eq!(res.reason(), ColorReason::NotTerminal);
+
+        let res = auto(true, &[("TERM", "xterm"), ("CLICOLOR", "0")]);
+        assert_eq!(res.reason(), ColorReason::CliColorOff);
+        assert!(!res.enabled());
+        let res = auto(true, &[("TERM", "xterm"), ("CLICOLOR", "1")]);
+        assert_eq!(res.reason(), ColorReason::Terminal);
+        let res = auto(true, &[("CLICOLOR", "0"), ("CLICOLOR_FORCE", "1")]);
+        assert_eq!(res.reason(), ColorReason::CliColorForce);
+
+        // NO_COLOR takes precedence over everything else.
+        let res = auto(true, &[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]);
+        assert_eq!(res.reason(), ColorReason::NoColor);
+        assert!(!res.enabled());
+    }
+
     #[test]
     fn test_simple_parse_ok() {
         let color = "green".parse::<Color>();
@@ -2484,22 +4047,229 @@ mod tests {
     #[test]
     fn test_hex_parse_ok() {
         let color = "#000000".parse::<Color>();
-        assert_eq!(color, Ok(Color::Hex("#000000")));
+        assert_eq!(color, Ok(Color::Hex("#000000".to_string())));
+
+        let color = "#89b4fa".parse::<Color>();
+        assert_eq!(color, Ok(Color::Hex("#89B4FA".to_string())));
+    }
+
+    #[test]
+    fn test_var_ansi_write_hex() {
+        let mut buf = Ansi::new(vec![]);
+        let _ = buf.write_color(true, &Color::Hex("#FFF".to_string()), false);
+        assert_eq!(buf.0, b"\x1B[38;2;255;255;255m");
+
+        let mut buf = Ansi::new(vec![]);
+        let _ =
+            buf.write_color(false, &Color::Hex("#89B4FA".to_string()), false);
+        assert_eq!(buf.0, b"\x1B[48;2;137;180;250m");
     }
 
     #[test]
     fn test_hex_parse_err_bad_format() {
-        let color = "#0000".parse::<Color>();
+        let color = "#00000".parse::<Color>();
 
         assert_eq!(
             color,
             Err(ParseColorError {
                 kind: ParseColorErrorKind::InvalidHex,
-                given: "#0000".to_string(),
+                given: "#00000".to_string(),
             })
         );
     }
 
+    #[test]
+    fn test_hex_alpha() {
+        let color = "#89b4fa80".parse::<Color>().unwrap();
+        assert_eq!(color, Color::Hex("#89B4FA80".to_string()));
+        assert_eq!(color.alpha(), Some(0x80));
+        assert_eq!("#F008".parse::<Color>().unwrap().alpha(), Some(0x88));
+        assert_eq!("#F00".parse::<Color>().unwrap().alpha(), None);
+        // Malformed `Hex` values have no alpha component.
+        assert_eq!(Color::Hex("#éé".to_string()).alpha(), None);
+        assert_eq!(Color::Hex("#FF0éé".to_string()).alpha(), None);
+
+        // Writers ignore the alpha component.
+        let mut buf = Ansi::new(vec![]);
+        let _ = buf.write_color(true, &color, false);
+        assert_eq!(buf.0, b"\x1B[38;2;137;180;250m");
+
+        // The background is resolved with the default xterm palette.
+        let overlay = "#FFFFFF40".parse::<Color>().unwrap();
+        assert_eq!(overlay.composite(&Color::Red), Color::Rgb(218, 64, 64));
+    }
+
+    #[test]
+    fn test_color_function_parse_ok() {
+        let cases = [
+            ("hsl(210 80% 60%)", (71, 153, 235)),
+            ("hsl(0, 100%, 50%)", (255, 0, 0)),
+            ("HSV(120deg 100% 100%)", (0, 255, 0)),
+            ("hwb(0 60% 60%)", (128, 128, 128)),
+            ("lab(54.29 80.8 69.89)", (255, 0, 0)),
+            ("lch(54.29 106.84 40.85)", (255, 0, 0)),
+            ("oklab(0.62796 0.22486 0.12585)", (255, 0, 0)),
+            ("oklch(62.8% 0.2577 29.23 / 1)", (255, 0, 0)),
+            ("oklch(1 0 none)", (255, 255, 255)),
+            // Out of gamut, so the chroma is reduced.
+            ("oklch(0.7 0.5 150)", (0, 190, 88)),
+            ("oklch(70% 125% 150)", (0, 190, 88)),
+        ];
+        for &(given, (r, g, b)) in &cases {
+            assert_eq!(given.parse::<Color>(), Ok(Color::Rgb(r, g, b)));
+        }
+
+        // The alpha value is kept in a `Hex` color.
+        let color = "hwb(0.5turn 10% 20% / 50%)".parse::<Color>();
+        assert_eq!(color, Ok(Color::Hex("#1ACCCC80".to_string())));
+        let color = "hsl(0, 100%, 50%, 0.25)".parse::<Color>();
+        assert_eq!(color, Ok(Color::Hex("#FF000040".to_string())));
+
+        let spec = "fg:hsl(0 100% 50%) bg:oklch(0 0 0)".parse::<ColorSpec>();
+        let mut expected = ColorSpec::new();
+        expected
+            .set_fg(Some(Color::Rgb(255, 0, 0)))
+            .set_bg(Some(Color::Rgb(0, 0, 0)));
+        assert_eq!(spec, Ok(expected));
+    }
+
+    #[test]
+    fn test_color_function_parse_err() {
+        let out_of_range = |component, range| {
+            ParseColorErrorKind::ComponentOutOfRange { component, range }
+        };
+        let cases = [
+            (
+                "hsl(0 120% 50%)",
+                out_of_range("saturation", "between 0% and 100%"),
+            ),
+            (
+                "hwb(0 0% -1%)",
+                out_of_range("blackness", "between 0% and 100%"),
+            ),
+            ("lab(101 0 0)", out_of_range("lightness", "between 0 and 100")),
+            ("oklch(1.5 0 0)", out_of_range("lightness", "between 0 and 1")),
+            ("lch(50 -1 0)", out_of_range("chroma", "at least 0")),
+            ("hsl(0 10% 50% / 2)", out_of_range("alpha", "between 0 and 1")),
+            ("hsl(1 2)", ParseColorErrorKind::InvalidFunction),
+            ("hsl(0 100% 50% 0.5)", ParseColorErrorKind::InvalidFunction),
+            ("hsl(0, 100%, 50% / 0.5)", ParseColorErrorKind::InvalidFunction),
+            ("hsl(0, 100% 50%)", ParseColorErrorKind::InvalidFunction),
+            ("hsl(a b c)", ParseColorErrorKind::InvalidFunction),
+            ("hsl(0 0% 0%", ParseColorErrorKind::InvalidFunction),
+            ("foo(1 2 3)", ParseColorErrorKind::InvalidFunction),
+        ];
+        for (given, kind) in cases {
+            assert_eq!(
+                given.parse::<Color>(),
+                Err(ParseColorError { kind, given: given.to_string() })
+            );
+        }
+
+        let err = "hsl(0 120% 50%)".parse::<Color>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the saturation of 'hsl(0 120% 50%)' is out of range, should be \
+             between 0% and 100%"
+        );
+    }
+
+    #[cfg(feature = "named-colors")]
+    #[test]
+    fn test_named_color_parse() {
+        assert_eq!("orange".parse::<Color>(), Ok(Color::Rgb(255, 165, 0)));
+        assert_eq!(
+            "Slate_Gray".parse::<Color>(),
+            Ok(Color::Rgb(112, 128, 144))
+        );
+        // The base colors keep their ANSI meaning.
+        assert_eq!("Green".parse::<Color>(), Ok(Color::Green));
+        // Only a hyphen after `light` makes a bright color.
+        for given in ["lightgreen", "light green", "Light_Green"] {
+            assert_eq!(given.parse::<Color>(), Ok(Color::Rgb(144, 238, 144)));
+        }
+        assert_eq!("light-green".parse::<Color>(), Ok(Color::BrightGreen));
+
+        let err = "oragne".parse::<Color>().unwrap_err();
+        assert_eq!(
+            err,
+            ParseColorError {
+                kind: ParseColorErrorKind::InvalidName,
+                given: "oragne".to_string(),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "unrecognized color name 'oragne', did you mean 'orange'?"
+        );
+        let err = "xyzzy".parse::<Color>().unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("or a CSS color name such as 'orange'"));
+    }
+
+    #[test]
+    fn test_spec_parse_ok() {
+        let spec = "fg:red bg:#222 bold underline".parse::<ColorSpec>();
+        let mut expected = ColorSpec::new();
+        expected
+            .set_fg(Some(Color::Red))
+            .set_bg(Some(Color::Hex("#222".to_string())))
+            .set_bold(true)
+            .set_underline(true);
+        assert_eq!(spec, Ok(expected));
+
+        let spec = "  FG:rgb(1, 2, 3)\tbold nobold no-reset italic  "
+            .parse::<ColorSpec>();
+        let mut expected = ColorSpec::new();
+        expected
+            .set_fg(Some(Color::Rgb(1, 2, 3)))
+            .set_reset(false)
+            .set_italic(true);
+        assert_eq!(spec, Ok(expected));
+
+        assert_eq!("".parse::<ColorSpec>(), Ok(ColorSpec::new()));
+    }
+
+    #[test]
+    fn test_spec_parse_err() {
+        let spec = "bold fg:nope".parse::<ColorSpec>();
+        assert_eq!(
+            spec,
+            Err(ParseColorSpecError {
+                kind: ParseColorSpecErrorKind::InvalidColor(ParseColorError {
+                    kind: ParseColorErrorKind::InvalidName,
+                    given: "nope".to_string(),
+                }),
+                given: "fg:nope".to_string(),
+            })
+        );
+
+        let err = "bold blinky".parse::<ColorSpec>().unwrap_err();
+        assert_eq!(err.invalid(), "blinky");
+        assert_eq!(err.kind, ParseColorSpecErrorKind::UnknownAttribute);
+
+        let err = "xx:red".parse::<ColorSpec>().unwrap_err();
+        assert_eq!(err.invalid(), "xx:red");
+        assert_eq!(err.kind, ParseColorSpecErrorKind::UnknownKey);
+    }
+
+    #[test]
+    fn test_spec_display_round_trip() {
+        for spec in all_attributes() {
+            let text = spec.to_string();
+            assert_eq!(text.parse::<ColorSpec>(), Ok(spec), "{:?}", text);
+        }
+
+        let mut spec = ColorSpec::new();
+        spec.set_fg(Some(Color::Rgb(1, 2, 3)))
+            .set_bg(Some(Color::Ansi256(236)))
+            .set_dimmed(true)
+            .set_reset(false);
+        assert_eq!(spec.to_string(), "fg:rgb(1,2,3) bg:236 dimmed noreset");
+        assert_eq!(spec.to_string().parse::<ColorSpec>(), Ok(spec));
+    }
+
     #[test]
     fn test_var_ansi_write_rgb() {
         let mut buf = Ansi::new(vec![]);
@@ -2525,6 +4295,152 @@ mod tests {
         assert_eq!(buf.0, b"");
     }
 
+    #[test]
+    fn test_extra_attributes() {
+        let mut spec = ColorSpec::new();
+        spec.set_blink(true)
+            .set_reverse(true)
+            .set_hidden(true)
+            .set_overline(true);
+        let mut buf = Ansi::new(vec![]);
+        buf.set_color(&spec).unwrap();
+        assert_eq!(buf.0, b"\x1B[0;5;7;8;53m");
+        assert_eq!(spec.to_string(), "blink reverse hidden overline");
+    }
+
+    #[test]
+    fn test_single_sgr_sequence() {
+        let mut spec = ColorSpec::new();
+        spec.set_bold(true)
+            .set_italic(true)
+            .set_fg(Some(Color::Rgb(255, 0, 0)))
+            .set_bg(Some(Color::Ansi256(236)));
+        let mut buf = Ansi::new(vec![]);
+        buf.set_color(&spec).unwrap();
+        assert_eq!(buf.0, b"\x1B[0;1;3;38;2;255;0;0;48;5;236m");
+
+        spec.set_fg(Some(Color::Red))
+            .set_bg(Some(Color::Blue))
+            .set_intense(true);
+        let mut buf = Ansi::with_color_level(vec![], ColorLevel::Ansi16);
+        buf.set_color(&spec).unwrap();
+        assert_eq!(buf.0, b"\x1B[0;1;3;91;104m");
+    }
+
+    #[test]
+    fn test_bright_colors() {
+        for given in ["bright-red", "BrightRed", "bright red", "light-red"] {
+            assert_eq!(given.parse::<Color>(), Ok(Color::BrightRed));
+        }
+        assert_eq!("light-white".parse::<Color>(), Ok(Color::BrightWhite));
+        // Names with a space or an underscore that aren't CSS colors.
+        let cases = [
+            ("light red", Color::BrightRed),
+            ("Light_Magenta", Color::BrightMagenta),
+            ("light black", Color::BrightBlack),
+            ("light white", Color::BrightWhite),
+        ];
+        for (given, color) in cases {
+            assert_eq!(given.parse::<Color>(), Ok(color));
+        }
+        assert_eq!(Color::BrightMagenta.to_string(), "bright-magenta");
+        assert!("bright-orange".parse::<Color>().is_err());
+
+        let mut spec = ColorSpec::new();
+        spec.set_fg(Some(Color::BrightRed))
+            .set_bg(Some(Color::BrightBlack))
+            .set_intense(true);
+        for level in [ColorLevel::Ansi16, ColorLevel::TrueColor] {
+            let mut buf = Ansi::with_color_level(vec![], level);
+            buf.set_color(&spec).unwrap();
+            assert_eq!(buf.0, b"\x1B[0;91;100m");
+        }
+
+        // Intense named colors are written the same way at every level.
+        spec.set_bg(Some(Color::Blue));
+        for level in [ColorLevel::Ansi256, ColorLevel::TrueColor] {
+            let mut buf = Ansi::with_color_level(vec![], level);
+            buf.set_color(&spec).unwrap();
+            assert_eq!(buf.0, b"\x1B[0;91;104m");
+        }
+
+        // Unlike intense, a bright color only affects the color it's used
+        // for.
+        spec.set_bg(Some(Color::Black)).set_intense(false);
+        let mut buf = Ansi::new(vec![]);
+        buf.set_color(&spec).unwrap();
+        assert_eq!(buf.0, b"\x1B[0;91;40m");
+    }
+
+    #[test]
+    fn test_default_color() {
+        assert_eq!("Default".parse::<Color>(), Ok(Color::Default));
+        assert_eq!(Color::Default.to_string(), "default");
+
+        // Switching back to the default colors keeps other attributes.
+        let spec = "fg:default bg:default bold noreset".parse::<ColorSpec>();
+        let mut buf = Ansi::new(vec![]);
+        buf.set_color(&spec.unwrap()).unwrap();
+        assert_eq!(buf.0, b"\x1B[1;39;49m");
+
+        let spec = "underline ul:default noreset".parse::<ColorSpec>();
+        let mut buf = Ansi::with_color_level(vec![], ColorLevel::Ansi256);
+        buf.set_color(&spec.unwrap()).unwrap();
+        assert_eq!(buf.0, b"\x1B[4;59m");
+    }
+
+    #[test]
+    fn test_underline_style_and_color() {
+        let mut spec = ColorSpec::new();
+        spec.set_underline(true)
+            .set_underline_style(UnderlineStyle::Curly)
+            .set_underline_color(Some(Color::Rgb(255, 0, 0)))
+            .set_reset(false);
+        let mut buf = Ansi::new(vec![]);
+        buf.set_color(&spec).unwrap();
+        assert_eq!(buf.0, b"\x1B[4:3;58;2;255;0;0m");
+
+        let mut buf = Ansi::with_color_level(vec![], ColorLevel::Ansi256);
+        buf.set_color(&spec).unwrap();
+        assert_eq!(buf.0, b"\x1B[4:3;58;5;196m");
+
+        let mut buf = Ansi::new(vec![]);
+        buf.set_underline_styles(false);
+        buf.set_color(&spec).unwrap();
+        assert_eq!(buf.0, b"\x1B[4m");
+
+        spec.set_underline_color(Some(Color::Red));
+        let mut buf = Ansi::new(vec![]);
+        buf.set_color(&spec).unwrap();
+        assert_eq!(buf.0, b"\x1B[4:3;58;5;1m");
+
+        // The style has no effect unless underlining is enabled.
+        spec.set_underline(false);
+        let mut buf = Ansi::new(vec![]);
+        buf.set_color(&spec).unwrap();
+        assert_eq!(buf.0, b"\x1B[58;5;1m");
+    }
+
+    #[test]
+    fn test_underline_spec_round_trip() {
+        let spec = "ul:#f00 underline:dashed".parse::<ColorSpec>().unwrap();
+        assert!(spec.underline());
+        assert_eq!(spec.underline_style(), UnderlineStyle::Dashed);
+        assert_eq!(spec.underline_color(), Some(&Color::Hex("#F00".into())));
+        assert_eq!(spec.to_string(), "ul:#F00 underline:dashed");
+
+        let mut spec = ColorSpec::new();
+        spec.set_underline_style(UnderlineStyle::Double);
+        assert!(!spec.is_none());
+        assert_eq!(spec.to_string(), "underline:double nounderline");
+        assert_eq!(spec.to_string().parse::<ColorSpec>(), Ok(spec.clone()));
+        spec.clear();
+        assert!(spec.is_none());
+
+        let err = "underline:wavy".parse::<ColorSpec>().unwrap_err();
+        assert_eq!(err.kind, ParseColorSpecErrorKind::UnknownUnderlineStyle);
+    }
+
     #[test]
     fn test_var_ansi_write_256() {
         let mut buf = Ansi::new(vec![]);
@@ -2536,31 +4452,65 @@ mod tests {
         assert_eq!(buf.0, b"\x1B[48;5;208m");
     }
 
+    #[test]
+    fn test_color_level_256() {
+        let mut buf = Ansi::with_color_level(vec![], ColorLevel::Ansi256);
+        let _ = buf.write_color(true, &Color::Rgb(255, 0, 0), false);
+        assert_eq!(buf.0, b"\x1B[38;5;196m");
+
+        let mut buf = Ansi::with_color_level(vec![], ColorLevel::Ansi256);
+        let _ = buf.write_color(false, &Color::Hex("#222".to_string()), false);
+        assert_eq!(buf.0, b"\x1B[48;5;235m");
+
+        let mut buf = Ansi::with_color_level(vec![], ColorLevel::Ansi256);
+        let _ = buf.write_color(true, &Color::Red, true);
+        assert_eq!(buf.0, b"\x1B[91m");
+    }
+
+    #[test]
+    fn test_color_level_16() {
+        let mut buf = Ansi::with_color_level(vec![], ColorLevel::Ansi16);
+        let _ = buf.write_color(true, &Color::Rgb(250, 10, 10), false);
+        assert_eq!(buf.0, b"\x1B[91m");
+
+        let mut buf = Ansi::with_color_level(vec![], ColorLevel::Ansi16);
+        let _ = buf.write_color(false, &Color::Ansi256(196), false);
+        assert_eq!(buf.0, b"\x1B[101m");
+
+        let mut buf = Ansi::with_color_level(vec![], ColorLevel::Ansi16);
+        let _ = buf.write_color(false, &Color::Ansi256(4), false);
+        assert_eq!(buf.0, b"\x1B[44m");
+
+        let mut buf = Ansi::with_color_level(vec![], ColorLevel::Ansi16);
+        let _ = buf.write_color(true, &Color::Green, true);
+        assert_eq!(buf.0, b"\x1B[92m");
+
+        let mut buf = Ansi::with_color_level(vec![], ColorLevel::Ansi16);
+        let _ = buf.write_color(true, &Color::Green, false);
+        assert_eq!(buf.0, b"\x1B[32m");
+    }
+
     fn all_attributes() -> Vec<ColorSpec> {
         let mut result = Vec::new();
         for fg in [None, Some(Color::Red)] {
             for bg in [None, Some(Color::Red)] {
-                for bold in [false, true] {
-                    for underline in [false, true] {
-                        for intense in [false, true] {
-                            for italic in [false, true] {
-                                for strikethrough in [false, true] {
-                                    for dimmed in [false, true] {
-                                        let mut color = ColorSpec::new();
-                                        color.set_fg(fg.clone());
-                                        color.set_bg(bg.clone());
-                                        color.set_bold(bold);
-                                        color.set_underline(underline);
-                                        color.set_intense(intense);
-                                        color.set_italic(italic);
-                                        color.set_dimmed(dimmed);
-                                        color.set_strikethrough(strikethrough);
-                                        result.push(color);
-                                    }
-                                }
-                            }
-                        }
-                    }
+                // Each bit of `attrs` toggles one of the boolean attributes.
+                for attrs in 0u32..(1 << 10) {
+                    let on = |bit: u32| attrs & (1 << bit) != 0;
+                    let mut color = ColorSpec::new();
+                    color.set_fg(fg.clone());
+                    color.set_bg(bg.clone());
+                    color.set_bold(on(0));
+                    color.set_underline(on(1));
+                    color.set_intense(on(2));
+                    color.set_italic(on(3));
+                    color.set_dimmed(on(4));
+                    color.set_strikethrough(on(5));
+                    color.set_blink(on(6));
+                    color.set_reverse(on(7));
+                    color.set_hidden(on(8));
+                    color.set_overline(on(9));
+                    result.push(color);
                 }
             }
         }
@@ -0,0 +1,270 @@
+use std::fmt;
+use std::io;
+
+use crate::{ColorSpec, WriteColor};
+
+/// Write formatted text with inline style markup to a `WriteColor`.
+///
+/// This works like `write!`, except that the format string may contain style
+/// markup in braces, which is turned into calls to `set_color` and `reset`:
+///
+/// * `{fg:red bold}` sets the style to the given color specification, in
+///   the textual form accepted by `ColorSpec`'s `FromStr` implementation.
+///   Like any `ColorSpec`, it replaces the current style unless it contains
+///   `noreset`.
+/// * `{/}` resets the style.
+/// * `{}` is replaced by the next argument, which must implement `Display`.
+/// * `{{` and `}}` are replaced by `{` and `}`.
+///
+/// If a style is still set at the end, the writer is reset. Other format
+/// specifications, such as `{:?}` or `{name}`, aren't supported, since the
+/// format string is parsed when the macro is called.
+///
+/// The format string is parsed before anything is written. If it is
+/// malformed, if a style is invalid, or if the number of arguments doesn't
+/// match the number of `{}` placeholders, an error of kind
+/// `io::ErrorKind::InvalidInput` describing the problem is returned.
+///
+/// # Example
+///
+/// ```
+/// use termcolor2::{write_styled, Recorder};
+///
+/// let mut rec = Recorder::new();
+/// write_styled!(rec, "{fg:red bold}error{/}: {}", "file not found")?;
+/// assert_eq!(rec.render(), "[fg:red bold]error[/]: file not found");
+///
+/// let err = write_styled!(rec, "{fg:nope}oops{/}").unwrap_err();
+/// assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[macro_export]
+macro_rules! write_styled {
+    ($wtr:expr, $fmt:expr $(, $arg:expr)* $(,)?) => {{
+        use $crate::__WriteStyled as _;
+        $wtr.__write_styled(
+            $fmt,
+            &[$(&$arg as &dyn ::std::fmt::Display),*],
+            false,
+        )
+    }};
+}
+
+/// Write formatted text with inline style markup to a `WriteColor`, followed
+/// by a new line.
+///
+/// This is the same as [`write_styled!`], except that a new line is written
+/// at the end, after the writer is reset.
+///
+/// # Example
+///
+/// ```
+/// use termcolor2::{writeln_styled, Recorder};
+///
+/// let mut rec = Recorder::new();
+/// writeln_styled!(rec, "{fg:green}ok{/} {} tests", 3)?;
+/// assert_eq!(rec.render(), "[fg:green]ok[/] 3 tests\n");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[macro_export]
+macro_rules! writeln_styled {
+    ($wtr:expr, $fmt:expr $(, $arg:expr)* $(,)?) => {{
+        use $crate::__WriteStyled as _;
+        $wtr.__write_styled(
+            $fmt,
+            &[$(&$arg as &dyn ::std::fmt::Display),*],
+            true,
+        )
+    }};
+}
+
+/// A piece of a format string with style markup.
+#[derive(Debug, PartialEq)]
+enum Piece<'a> {
+    Text(&'a str),
+    /// A literal `{` or `}`.
+    Brace(&'a str),
+    Arg,
+    Style(ColorSpec),
+    Reset,
+}
+
+/// The implementation of `write_styled!` and `writeln_styled!`.
+///
+/// This is a method, so that the macros can take the writer by reference
+/// like `write!` does.
+#[doc(hidden)]
+pub trait __WriteStyled {
+    fn __write_styled(
+        &mut self,
+        fmt: &str,
+        args: &[&dyn fmt::Display],
+        newline: bool,
+    ) -> io::Result<()>;
+}
+
+impl<W: WriteColor + ?Sized> __WriteStyled for W {
+    fn __write_styled(
+        &mut self,
+        fmt: &str,
+        args: &[&dyn fmt::Display],
+        newline: bool,
+    ) -> io::Result<()> {
+        write_styled(self, fmt, args, newline)
+    }
+}
+
+fn write_styled<W: WriteColor + ?Sized>(
+    wtr: &mut W,
+    fmt: &str,
+    args: &[&dyn fmt::Display],
+    newline: bool,
+) -> io::Result<()> {
+    let pieces = parse(fmt)
+        .map_err(|msg| io::Error::new(io::ErrorKind::InvalidInput, msg))?;
+    let wanted = pieces.iter().filter(|p| **p == Piece::Arg).count();
+    if wanted != args.len() {
+        let msg = format!(
+            "format string {:?} has {} placeholders, but {} arguments \
+             were given",
+            fmt,
+            wanted,
+            args.len()
+        );
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+    }
+
+    let mut args = args.iter();
+    let mut styled = false;
+    for piece in &pieces {
+        match *piece {
+            Piece::Text(text) | Piece::Brace(text) => {
+                wtr.write_all(text.as_bytes())?
+            }
+            Piece::Arg => {
+                if let Some(arg) = args.next() {
+                    write!(wtr, "{}", arg)?;
+                }
+            }
+            Piece::Style(ref spec) => {
+                wtr.set_color(spec)?;
+                styled = true;
+            }
+            Piece::Reset => {
+                wtr.reset()?;
+                styled = false;
+            }
+        }
+    }
+    if styled {
+        wtr.reset()?;
+    }
+    if newline {
+        wtr.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Split a format string into its pieces, or return a description of why it
+/// is invalid.
+fn parse(fmt: &str) -> Result<Vec<Piece<'_>>, String> {
+    let mut pieces = vec![];
+    let mut rest = fmt;
+    while let Some(i) = rest.find(['{', '}']) {
+        if i > 0 {
+            pieces.push(Piece::Text(&rest[..i]));
+        }
+        let after = &rest[i + 1..];
+        if rest[i..].starts_with('}') {
+            if !after.starts_with('}') {
+                return Err(format!(
+                    "unmatched '}}' in format string {:?}",
+                    fmt
+                ));
+            }
+            pieces.push(Piece::Brace("}"));
+            rest = &after[1..];
+            continue;
+        }
+        if let Some(after) = after.strip_prefix('{') {
+            pieces.push(Piece::Brace("{"));
+            rest = after;
+            continue;
+        }
+        let end = match after.find(['{', '}']) {
+            Some(end) if after[end..].starts_with('}') => end,
+            _ => {
+                return Err(format!(
+                    "unterminated '{{' in format string {:?}",
+                    fmt
+                ))
+            }
+        };
+        let markup = &after[..end];
+        pieces.push(match markup.trim() {
+            "" => Piece::Arg,
+            "/" => Piece::Reset,
+            spec => Piece::Style(spec.parse().map_err(|err| {
+                format!("invalid style markup '{{{}}}': {}", markup, err)
+            })?),
+        });
+        rest = &after[end + 1..];
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+    Ok(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::{Ansi, Recorder};
+
+    #[test]
+    fn markup() {
+        let mut rec = Recorder::new();
+        write_styled!(rec, "{fg:red}a{bold noreset}b{/}c{underline}d")
+            .unwrap();
+        assert_eq!(
+            rec.render(),
+            "[fg:red]a[/][fg:red bold]b[/]c[underline]d[/]"
+        );
+
+        let mut wtr = Ansi::new(vec![]);
+        writeln_styled!(wtr, "{{{}}} {fg:blue}{}{/}", 1, "x",).unwrap();
+        assert_eq!(wtr.into_inner(), b"{1} \x1B[0;34mx\x1B[0m\n");
+    }
+
+    #[test]
+    fn through_references() {
+        fn inner(wtr: &mut Recorder) -> io::Result<()> {
+            write_styled!(wtr, "{italic}{}", "p")
+        }
+        let mut rec = Recorder::new();
+        inner(&mut rec).unwrap();
+        assert_eq!(rec.render(), "[italic]p[/]");
+    }
+
+    #[test]
+    fn errors() {
+        let mut rec = Recorder::new();
+        let err = |res: io::Result<()>| {
+            let err = res.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            err.to_string()
+        };
+        assert!(err(write_styled!(rec, "ok {fg:nope}x"))
+            .starts_with("invalid style markup '{fg:nope}': "));
+        assert!(err(write_styled!(rec, "{fg:red")).contains("unterminated"));
+        assert!(err(write_styled!(rec, "a}b")).contains("unmatched"));
+        assert_eq!(
+            err(write_styled!(rec, "{} {}", 1)),
+            "format string \"{} {}\" has 2 placeholders, but 1 arguments \
+             were given"
+        );
+        // Nothing is written when the format string is invalid.
+        assert!(rec.segments().is_empty());
+    }
+}
@@ -0,0 +1,338 @@
+use std::io;
+
+use crate::{ColorSpec, HyperlinkSpec, WriteColor};
+
+/// Satisfies `WriteColor` by wrapping another `WriteColor` and only writing
+/// the difference between the current style and each new one.
+///
+/// `MinimalColor` remembers the effective style of the text written so far.
+/// When a new `ColorSpec` is set, nothing is written if the effective style
+/// doesn't change. Otherwise, only the attributes and colors that were
+/// turned on or changed are set on the wrapped writer, without a reset.
+///
+/// Attributes and colors that were turned off can only be expressed with
+/// ANSI escape sequences (e.g., `22` for bold and dimmed, `23` for italic,
+/// `24` for underline or `39` for the foreground color). If the wrapped
+/// writer is an [`Ansi`](crate::Ansi) writer, including one used by a
+/// `StandardStream` or a `Buffer`, then those are written in the same escape
+/// sequence as the changes. Otherwise, or if doing so would take more codes
+/// than a reset followed by the new style, the new style is set in full.
+///
+/// The output renders the same as writing each `ColorSpec` directly.
+///
+/// Note that `MinimalColor` assumes that it is the only one changing the
+/// style of the wrapped writer, and that the writer starts without any
+/// style.
+#[derive(Clone, Debug)]
+pub struct MinimalColor<W> {
+    wtr: W,
+    current: ColorSpec,
+}
+
+impl<W: WriteColor> MinimalColor<W> {
+    /// Create a new writer that only writes style changes to the given
+    /// writer.
+    pub fn new(wtr: W) -> MinimalColor<W> {
+        MinimalColor { wtr, current: ColorSpec::new() }
+    }
+
+    /// Consume this `MinimalColor` value and return the inner writer.
+    pub fn into_inner(self) -> W {
+        self.wtr
+    }
+
+    /// Return a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.wtr
+    }
+
+    /// Return a mutable reference to the inner writer.
+    ///
+    /// Changing the style of the inner writer directly will cause
+    /// `MinimalColor` to lose track of the effective style.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.wtr
+    }
+
+    /// Return the effective style of text written now.
+    pub fn current(&self) -> &ColorSpec {
+        &self.current
+    }
+}
+
+impl<W: WriteColor> io::Write for MinimalColor<W> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.wtr.write(buf)
+    }
+
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.wtr.write_all(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for MinimalColor<W> {
+    #[inline]
+    fn supports_color(&self) -> bool {
+        self.wtr.supports_color()
+    }
+
+    #[inline]
+    fn supports_hyperlinks(&self) -> bool {
+        self.wtr.supports_hyperlinks()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        let next = self.current.apply(spec);
+        if next == self.current {
+            return Ok(());
+        }
+        if next.is_none() {
+            return self.reset();
+        }
+
+        let (offs, delta) = transition(&self.current, &next);
+        if offs.is_empty() {
+            self.wtr.set_color(&delta)?;
+        } else if offs.len() + cost(&delta) < 1 + cost(&next) {
+            self.wtr.set_color_after_sgr(Token(()), &offs, &delta, &next)?;
+        } else {
+            self.wtr.set_color(&next)?;
+        }
+        self.current = next;
+        Ok(())
+    }
+
+    #[inline]
+    fn set_hyperlink(&mut self, link: &HyperlinkSpec) -> io::Result<()> {
+        self.wtr.set_hyperlink(link)
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        if self.current.is_none() {
+            return Ok(());
+        }
+        self.wtr.reset()?;
+        self.current.clear();
+        Ok(())
+    }
+
+    #[inline]
+    fn is_synchronous(&self) -> bool {
+        self.wtr.is_synchronous()
+    }
+
+    #[inline]
+    fn is_ansi(&self) -> bool {
+        self.wtr.is_ansi()
+    }
+}
+
+/// Returns the number of escape sequence parameters needed to set the given
+/// style, not counting the reset.
+fn cost(spec: &ColorSpec) -> usize {
+    let attrs = spec.attributes().iter().filter(|&&(_, yes)| yes).count();
+    // Intense doesn't have its own code. It only changes how colors are
+    // written.
+    let intense = spec.intense as usize;
+    let colors = spec.fg_color.is_some() as usize
+        + spec.bg_color.is_some() as usize
+        + spec.underline_color.is_some() as usize;
+    attrs - intense + colors
+}
+
+/// Computes how to get from the `old` style to the `new` one without a reset.
+///
+/// This returns the ANSI codes that turn off what `new` no longer has, along
+/// with a non-resetting `ColorSpec` of what needs to be turned on or changed
+/// afterwards.
+fn transition(
+    old: &ColorSpec,
+    new: &ColorSpec,
+) -> (Vec<&'static str>, ColorSpec) {
+    let mut offs = vec![];
+    let mut delta = ColorSpec::new();
+    delta.set_reset(false).set_intense(new.intense);
+
+    // Bold and dimmed are both turned off by the same code, so if either is
+    // turned off, the other must be turned on again if it remains.
+    let (mut bold, mut dimmed) = (old.bold, old.dimmed);
+    if (old.bold && !new.bold) || (old.dimmed && !new.dimmed) {
+        offs.push("22");
+        bold = false;
+        dimmed = false;
+    }
+    delta.set_bold(new.bold && !bold);
+    delta.set_dimmed(new.dimmed && !dimmed);
+
+    let toggles = [
+        (old.italic, new.italic, "23"),
+        (old.blink, new.blink, "25"),
+        (old.reverse, new.reverse, "27"),
+        (old.hidden, new.hidden, "28"),
+        (old.strikethrough, new.strikethrough, "29"),
+        (old.overline, new.overline, "55"),
+    ];
+    for &(was, is, off) in &toggles {
+        if was && !is {
+            offs.push(off);
+        }
+    }
+    delta.set_italic(new.italic && !old.italic);
+    delta.set_blink(new.blink && !old.blink);
+    delta.set_reverse(new.reverse && !old.reverse);
+    delta.set_hidden(new.hidden && !old.hidden);
+    delta.set_strikethrough(new.strikethrough && !old.strikethrough);
+    delta.set_overline(new.overline && !old.overline);
+
+    if old.underline && !new.underline {
+        offs.push("24");
+    } else if new.underline
+        && (!old.underline || old.underline_style != new.underline_style)
+    {
+        delta.set_underline(true).set_underline_style(new.underline_style);
+    }
+
+    // Colors are written differently depending on intensity, so all of them
+    // need to be written again if it changed.
+    let intense_changed = old.intense != new.intense;
+    let colors = [
+        (&old.fg_color, &new.fg_color, "39"),
+        (&old.bg_color, &new.bg_color, "49"),
+        (&old.underline_color, &new.underline_color, "59"),
+    ];
+    for (i, &(was, is, off)) in colors.iter().enumerate() {
+        let changed = match (was, is) {
+            (Some(_), None) => {
+                offs.push(off);
+                continue;
+            }
+            (_, None) => continue,
+            (was, is) => was != is || intense_changed,
+        };
+        if changed {
+            match i {
+                0 => delta.set_fg(is.clone()),
+                1 => delta.set_bg(is.clone()),
+                _ => delta.set_underline_color(is.clone()),
+            };
+        }
+    }
+    (offs, delta)
+}
+
+/// Passed to `WriteColor::set_color_after_sgr`, so that it can only be called
+/// and overridden within this crate, since this type can't be named outside
+/// of it.
+#[derive(Clone, Copy, Debug)]
+pub struct Token(());
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::MinimalColor;
+    use crate::tests::spec;
+    use crate::{
+        Ansi, Buffer, ColorSpec, Recorder, UnderlineStyle, WriteColor,
+    };
+
+    fn ansi() -> MinimalColor<Ansi<Vec<u8>>> {
+        MinimalColor::new(Ansi::new(vec![]))
+    }
+
+    fn output(wtr: MinimalColor<Ansi<Vec<u8>>>) -> String {
+        String::from_utf8(wtr.into_inner().into_inner()).unwrap()
+    }
+
+    #[test]
+    fn unchanged_style_writes_nothing() {
+        let mut wtr = ansi();
+        wtr.set_color(&spec("fg:red bold")).unwrap();
+        write!(wtr, "a").unwrap();
+        wtr.set_color(&spec("fg:red bold")).unwrap();
+        write!(wtr, "b").unwrap();
+        wtr.set_color(&spec("bold noreset")).unwrap();
+        write!(wtr, "c").unwrap();
+        assert_eq!(output(wtr), "\x1B[1;31mabc");
+    }
+
+    #[test]
+    fn additions_are_not_reset() {
+        let mut wtr = ansi();
+        wtr.set_color(&spec("fg:red")).unwrap();
+        wtr.set_color(&spec("fg:red bold")).unwrap();
+        wtr.set_color(&spec("fg:blue bold")).unwrap();
+        assert_eq!(output(wtr), "\x1B[31m\x1B[1m\x1B[34m");
+    }
+
+    #[test]
+    fn targeted_resets() {
+        let mut wtr = ansi();
+        wtr.set_color(&spec("fg:red bold italic underline")).unwrap();
+        wtr.set_color(&spec("fg:red italic underline")).unwrap();
+        wtr.set_color(&spec("italic underline")).unwrap();
+        assert_eq!(output(wtr), "\x1B[1;3;4;31m\x1B[22m\x1B[39m");
+
+        // Several attributes turned off at once share one escape sequence.
+        let mut wtr = ansi();
+        wtr.set_color(&spec("fg:red bold italic underline")).unwrap();
+        wtr.set_color(&spec("italic underline")).unwrap();
+        assert_eq!(output(wtr), "\x1B[1;3;4;31m\x1B[22;39m");
+
+        // Turning off bold keeps dimmed.
+        let mut wtr = ansi();
+        wtr.set_color(&spec("bold dimmed italic")).unwrap();
+        wtr.set_color(&spec("dimmed italic")).unwrap();
+        assert_eq!(output(wtr), "\x1B[1;2;3m\x1B[22;2m");
+
+        // The same holds for writers wrapping an `Ansi` writer.
+        let mut wtr = MinimalColor::new(Buffer::ansi());
+        wtr.set_color(&spec("bold dimmed italic")).unwrap();
+        wtr.set_color(&spec("dimmed italic")).unwrap();
+        assert_eq!(wtr.into_inner().as_slice(), b"\x1B[1;2;3m\x1B[22;2m");
+    }
+
+    #[test]
+    fn full_reset_when_shorter() {
+        let mut wtr = ansi();
+        wtr.set_color(&spec("fg:red bold italic underline")).unwrap();
+        wtr.set_color(&spec("fg:blue")).unwrap();
+        assert_eq!(output(wtr), "\x1B[1;3;4;31m\x1B[0;34m");
+
+        let mut wtr = ansi();
+        wtr.set_color(&spec("fg:red bold")).unwrap();
+        wtr.set_color(&ColorSpec::new()).unwrap();
+        wtr.reset().unwrap();
+        assert_eq!(output(wtr), "\x1B[1;31m\x1B[0m");
+    }
+
+    #[test]
+    fn underline_and_intensity_changes() {
+        let mut wtr = ansi();
+        wtr.set_color(&spec("fg:red underline")).unwrap();
+        let mut next = spec("fg:red");
+        next.set_underline(true).set_underline_style(UnderlineStyle::Curly);
+        wtr.set_color(&next).unwrap();
+        next.set_intense(true);
+        wtr.set_color(&next).unwrap();
+        assert_eq!(output(wtr), "\x1B[4;31m\x1B[4:3m\x1B[91m");
+    }
+
+    #[test]
+    fn non_ansi_writers_get_full_style() {
+        let mut wtr = MinimalColor::new(Recorder::new());
+        wtr.set_color(&spec("fg:red bold")).unwrap();
+        write!(wtr, "a").unwrap();
+        wtr.set_color(&spec("fg:red")).unwrap();
+        write!(wtr, "b").unwrap();
+        assert_eq!(wtr.current(), &spec("fg:red"));
+        assert_eq!(wtr.into_inner().render(), "[fg:red bold]a[/][fg:red]b[/]");
+    }
+}
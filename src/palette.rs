@@ -0,0 +1,346 @@
+/// Conversions between RGB colors and the xterm 256 and 16 color palettes.
+///
+/// The first 16 entries of the 256 color palette are the "system" colors,
+/// whose exact values depend on the terminal's theme. We use the xterm
+/// defaults for them. Entries 16-231 form a 6x6x6 color cube and entries
+/// 232-255 form a grayscale ramp.
+use crate::Color;
+
+/// A palette of concrete RGB values for rendering colors outside of a
+/// terminal, e.g., as HTML.
+///
+/// A palette assigns a value to each of the 256 colors of the xterm 256
+/// color palette. The first 8 of them are the named colors (e.g.,
+/// `Color::Red`) and the next 8 are their intense variants. A palette also
+/// has a default foreground and background color, which are used for text
+/// without a foreground or background color.
+///
+/// The default palette uses the xterm defaults, with light gray text on a
+/// black background.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Palette {
+    colors: [(u8, u8, u8); 256],
+    foreground: (u8, u8, u8),
+    background: (u8, u8, u8),
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        let mut colors = [(0, 0, 0); 256];
+        for (i, c) in colors.iter_mut().enumerate() {
+            *c = ansi256_to_rgb(i as u8);
+        }
+        Palette { colors, foreground: SYSTEM[7], background: SYSTEM[0] }
+    }
+}
+
+impl Palette {
+    /// Create a new palette with the xterm default colors.
+    pub fn new() -> Palette {
+        Palette::default()
+    }
+
+    /// Get the value of the given xterm 256 color index.
+    pub fn get(&self, index: u8) -> (u8, u8, u8) {
+        self.colors[index as usize]
+    }
+
+    /// Set the value of the given xterm 256 color index.
+    ///
+    /// Indices 0-7 are the named colors, from `Color::Black` to
+    /// `Color::White`, and indices 8-15 are their intense variants.
+    pub fn set(&mut self, index: u8, rgb: (u8, u8, u8)) -> &mut Palette {
+        self.colors[index as usize] = rgb;
+        self
+    }
+
+    /// Get the default foreground color.
+    pub fn foreground(&self) -> (u8, u8, u8) {
+        self.foreground
+    }
+
+    /// Set the default foreground color.
+    pub fn set_foreground(&mut self, rgb: (u8, u8, u8)) -> &mut Palette {
+        self.foreground = rgb;
+        self
+    }
+
+    /// Get the default background color.
+    pub fn background(&self) -> (u8, u8, u8) {
+        self.background
+    }
+
+    /// Set the default background color.
+    pub fn set_background(&mut self, rgb: (u8, u8, u8)) -> &mut Palette {
+        self.background = rgb;
+        self
+    }
+
+    /// Returns the value of the given color.
+    ///
+    /// Bright colors are indices 8-15. When `intense` is true, named colors
+    /// use their intense variant, as they do when written by `Ansi`. Colors
+    /// with RGB components are returned as is, and the default color is the
+    /// default foreground color.
+    pub fn resolve(&self, color: &Color, intense: bool) -> (u8, u8, u8) {
+        match (system_index(color, intense), color.rgb()) {
+            (Some(n), _) => self.get(n),
+            (None, Some(rgb)) => rgb,
+            (None, None) => match *color {
+                Color::Ansi256(n) => self.get(n),
+                _ => self.foreground,
+            },
+        }
+    }
+
+    /// Composites the given color over the given background color, using
+    /// this palette's values for colors that aren't RGB.
+    ///
+    /// Colors with an alpha component, e.g., `#FF000080`, are blended with
+    /// the background and returned as `Color::Rgb`, which terminals can
+    /// display. Other colors are opaque and returned as is. The alpha of the
+    /// background color is ignored, and `Color::Default` stands for this
+    /// palette's background color.
+    pub fn composite(&self, color: &Color, background: &Color) -> Color {
+        let alpha = match color.alpha() {
+            Some(alpha) => u32::from(alpha),
+            None => return color.clone(),
+        };
+        let (r, g, b) = self.resolve(color, false);
+        let (br, bg, bb) = match *background {
+            Color::Default => self.background,
+            ref c => self.resolve(c, false),
+        };
+        let blend = |over: u8, under: u8| {
+            let (over, under) = (u32::from(over), u32::from(under));
+            ((over * alpha + under * (255 - alpha) + 127) / 255) as u8
+        };
+        Color::Rgb(blend(r, br), blend(g, bg), blend(b, bb))
+    }
+}
+
+/// The xterm default values of the 16 system colors.
+const SYSTEM: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The intensity of each of the six steps along an axis of the color cube.
+const CUBE_STEPS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Returns the squared euclidean distance between two RGB colors.
+#[inline]
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let dr = a.0 as i32 - b.0 as i32;
+    let dg = a.1 as i32 - b.1 as i32;
+    let db = a.2 as i32 - b.2 as i32;
+    (dr * dr + dg * dg + db * db) as u32
+}
+
+/// Returns the index of the color cube step nearest to the given intensity.
+#[inline]
+fn nearest_cube_step(v: u8) -> usize {
+    match v {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (v as usize - 35) / 40,
+    }
+}
+
+/// Returns the RGB value of the given xterm 256 color index.
+pub fn ansi256_to_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..=15 => SYSTEM[n as usize],
+        16..=231 => {
+            let n = n - 16;
+            let r = CUBE_STEPS[(n / 36) as usize];
+            let g = CUBE_STEPS[((n / 6) % 6) as usize];
+            let b = CUBE_STEPS[(n % 6) as usize];
+            (r, g, b)
+        }
+        _ => {
+            let v = 8 + 10 * (n - 232);
+            (v, v, v)
+        }
+    }
+}
+
+/// Returns the xterm 256 color index nearest to the given RGB value.
+///
+/// Only the color cube and the grayscale ramp are considered, since the
+/// system colors are commonly redefined by terminal themes.
+pub fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let (ri, gi, bi) =
+        (nearest_cube_step(r), nearest_cube_step(g), nearest_cube_step(b));
+    let cube = (CUBE_STEPS[ri], CUBE_STEPS[gi], CUBE_STEPS[bi]);
+    let cube_index = (16 + 36 * ri + 6 * gi + bi) as u8;
+
+    let avg = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_index =
+        if avg < 8 { 0 } else { ((avg - 8 + 5) / 10).min(23) as u8 };
+    let v = 8 + 10 * gray_index;
+
+    if distance((v, v, v), (r, g, b)) < distance(cube, (r, g, b)) {
+        232 + gray_index
+    } else {
+        cube_index
+    }
+}
+
+/// Returns the index (0-15) of the system color nearest to the given RGB
+/// value.
+pub fn rgb_to_ansi16(r: u8, g: u8, b: u8) -> u8 {
+    let mut best = 0;
+    let mut best_distance = u32::MAX;
+    for (i, &c) in SYSTEM.iter().enumerate() {
+        let d = distance(c, (r, g, b));
+        if d < best_distance {
+            best = i as u8;
+            best_distance = d;
+        }
+    }
+    best
+}
+
+/// Returns the index (0-15) of the system color nearest to the given xterm
+/// 256 color index.
+pub fn ansi256_to_ansi16(n: u8) -> u8 {
+    if n < 16 {
+        return n;
+    }
+    let (r, g, b) = ansi256_to_rgb(n);
+    rgb_to_ansi16(r, g, b)
+}
+
+/// Returns the index (0-7) of one of the eight named colors.
+pub fn named_index(c: &Color) -> Option<u8> {
+    match *c {
+        Color::Black => Some(0),
+        Color::Red => Some(1),
+        Color::Green => Some(2),
+        Color::Yellow => Some(3),
+        Color::Blue => Some(4),
+        Color::Magenta => Some(5),
+        Color::Cyan => Some(6),
+        Color::White => Some(7),
+        _ => None,
+    }
+}
+
+/// Returns the index (8-15) of one of the eight bright colors.
+pub fn bright_index(c: &Color) -> Option<u8> {
+    match *c {
+        Color::BrightBlack => Some(8),
+        Color::BrightRed => Some(9),
+        Color::BrightGreen => Some(10),
+        Color::BrightYellow => Some(11),
+        Color::BrightBlue => Some(12),
+        Color::BrightMagenta => Some(13),
+        Color::BrightCyan => Some(14),
+        Color::BrightWhite => Some(15),
+        _ => None,
+    }
+}
+
+/// Returns the index (0-15) of the system color used for a named or bright
+/// color. Named colors use their intense variant when `intense` is true.
+pub fn system_index(c: &Color, intense: bool) -> Option<u8> {
+    match named_index(c) {
+        Some(n) if intense => Some(n + 8),
+        Some(n) => Some(n),
+        None => bright_index(c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ansi256_to_ansi16, ansi256_to_rgb, rgb_to_ansi16, rgb_to_ansi256,
+        Palette,
+    };
+    use crate::Color;
+
+    #[test]
+    fn cube_round_trip() {
+        for n in 16..=255 {
+            let (r, g, b) = ansi256_to_rgb(n);
+            assert_eq!(rgb_to_ansi256(r, g, b), n, "index {}", n);
+        }
+    }
+
+    #[test]
+    fn nearest_256() {
+        assert_eq!(rgb_to_ansi256(255, 0, 0), 196);
+        assert_eq!(rgb_to_ansi256(0x89, 0xB4, 0xFA), 111);
+        assert_eq!(rgb_to_ansi256(0x22, 0x22, 0x22), 235);
+    }
+
+    #[test]
+    fn nearest_16() {
+        assert_eq!(rgb_to_ansi16(250, 10, 10), 9);
+        assert_eq!(rgb_to_ansi16(190, 0, 0), 1);
+        assert_eq!(rgb_to_ansi16(10, 10, 10), 0);
+        assert_eq!(ansi256_to_ansi16(9), 9);
+        assert_eq!(ansi256_to_ansi16(196), 9);
+        assert_eq!(ansi256_to_ansi16(231), 15);
+    }
+
+    #[test]
+    fn palette_resolve() {
+        let mut palette = Palette::new();
+        assert_eq!(palette.resolve(&Color::Red, false), (205, 0, 0));
+        assert_eq!(palette.resolve(&Color::Red, true), (255, 0, 0));
+        assert_eq!(palette.resolve(&Color::BrightRed, false), (255, 0, 0));
+        assert_eq!(palette.resolve(&Color::Ansi256(196), false), (255, 0, 0));
+        assert_eq!(palette.resolve(&Color::Rgb(1, 2, 3), true), (1, 2, 3));
+
+        palette.set(1, (0xF3, 0x8B, 0xA8));
+        assert_eq!(palette.resolve(&Color::Red, false), (0xF3, 0x8B, 0xA8));
+        assert_eq!(
+            palette.resolve(&Color::Ansi256(1), true),
+            (0xF3, 0x8B, 0xA8)
+        );
+    }
+
+    #[test]
+    fn palette_composite() {
+        let mut palette = Palette::new();
+        let half_white = Color::Hex("#FFFFFF80".to_string());
+        assert_eq!(
+            palette.composite(&half_white, &Color::Black),
+            Color::Rgb(128, 128, 128)
+        );
+        assert_eq!(
+            palette.composite(&Color::Hex("#F00F".to_string()), &Color::Blue),
+            Color::Rgb(255, 0, 0)
+        );
+        assert_eq!(
+            palette.composite(&Color::Hex("#F000".to_string()), &Color::Blue),
+            Color::Rgb(0, 0, 238)
+        );
+
+        palette.set_background((0, 0, 100));
+        assert_eq!(
+            palette.composite(&half_white, &Color::Default),
+            Color::Rgb(128, 128, 178)
+        );
+        // Opaque colors are returned as is.
+        assert_eq!(palette.composite(&Color::Red, &Color::Blue), Color::Red);
+        let hex = Color::Hex("#123456".to_string());
+        assert_eq!(palette.composite(&hex, &Color::Blue), hex);
+    }
+}
@@ -0,0 +1,627 @@
+use std::io;
+
+use crate::strip::State;
+use crate::{Color, ColorSpec, HyperlinkSpec, UnderlineStyle};
+
+/// The maximum number of bytes of a single escape sequence that are kept.
+///
+/// Longer sequences, such as an operating system command that is never
+/// terminated, are still consumed but are otherwise ignored.
+const MAX_SEQUENCE_LEN: usize = 8192;
+
+/// An event produced by an [`AnsiParser`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum AnsiEvent<'a> {
+    /// Text to write with the current style and hyperlink.
+    ///
+    /// The text is passed through unchanged, so it may contain invalid UTF-8
+    /// and control characters such as new lines.
+    Text(&'a [u8]),
+    /// The style of the text that follows changed.
+    ///
+    /// The `ColorSpec` is the complete effective style, not just the change,
+    /// so it always resets. When the style was reset to the terminal's
+    /// default, the spec has no colors or attributes set.
+    Style(&'a ColorSpec),
+    /// A hyperlink was opened or closed via `OSC 8`.
+    Hyperlink(HyperlinkSpec<'a>),
+    /// A control sequence other than SGR, such as a cursor movement.
+    ///
+    /// This contains the bytes of the sequence after `ESC [`, i.e., its
+    /// parameters, intermediate bytes and final byte. For example, the
+    /// sequence that erases the current line, `ESC [ 2 K`, is `2K`.
+    Csi(&'a [u8]),
+}
+
+/// A streaming parser that decodes text written with ANSI escape sequences
+/// into text, style and hyperlink events.
+///
+/// Select Graphic Rendition (SGR) sequences are decoded into `ColorSpec`
+/// values. This includes the 8 standard colors, the 8 bright colors (as
+/// `Color::BrightRed` and the like), 256 colors and 24-bit colors, in both the
+/// `;` and `:` separated forms, for the foreground, background and underline.
+/// Every attribute that `ColorSpec` supports is decoded as well. Hyperlinks
+/// are decoded from `OSC 8` sequences such as the ones written by
+/// `Ansi::set_hyperlink`.
+///
+/// Other control sequences are passed along undecoded as
+/// [`AnsiEvent::Csi`] events.
+///
+/// The parser is tolerant of malformed input. Unknown or invalid SGR
+/// parameters are skipped, and all other escape sequences are consumed
+/// without producing any event. Escape sequences may be split across any
+/// number of calls to [`AnsiParser::feed`].
+///
+/// # Example
+///
+/// ```
+/// use termcolor2::{AnsiEvent, AnsiParser, Color};
+///
+/// let mut parser = AnsiParser::new();
+/// let mut styles = vec![];
+/// parser.feed(b"\x1B[1;31merror\x1B[0m", |event| {
+///     if let AnsiEvent::Style(spec) = event {
+///         styles.push(spec.clone());
+///     }
+///     Ok(())
+/// })?;
+/// assert_eq!(styles[0].fg(), Some(&Color::Red));
+/// assert!(styles[0].bold());
+/// assert!(styles[1].is_none());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct AnsiParser {
+    state: State,
+    /// The bytes of the escape sequence currently being parsed, starting
+    /// with `ESC`.
+    seq: Vec<u8>,
+    style: ColorSpec,
+    hyperlink: Option<Vec<u8>>,
+}
+
+impl Default for AnsiParser {
+    fn default() -> AnsiParser {
+        AnsiParser::new()
+    }
+}
+
+impl AnsiParser {
+    /// Create a new parser, starting with no style and no hyperlink.
+    pub fn new() -> AnsiParser {
+        AnsiParser {
+            state: State::Ground,
+            seq: vec![],
+            style: ColorSpec::new(),
+            hyperlink: None,
+        }
+    }
+
+    /// Return the effective style of the text parsed so far.
+    pub fn style(&self) -> &ColorSpec {
+        &self.style
+    }
+
+    /// Return the URI of the hyperlink currently open, if any.
+    pub fn hyperlink(&self) -> Option<&[u8]> {
+        self.hyperlink.as_deref()
+    }
+
+    /// Parse the given bytes, passing each resulting event to `emit` in
+    /// order.
+    ///
+    /// An escape sequence that is incomplete at the end of `bytes` is kept
+    /// until the next call. If `emit` returns an error, parsing stops and the
+    /// error is returned. The remaining bytes are not parsed.
+    pub fn feed<F>(&mut self, bytes: &[u8], mut emit: F) -> io::Result<()>
+    where
+        F: FnMut(AnsiEvent<'_>) -> io::Result<()>,
+    {
+        let mut start = None;
+        for (i, &b) in bytes.iter().enumerate() {
+            let (next, keep) = self.state.next(b);
+            let prev = self.state;
+            self.state = next;
+            if keep {
+                // Either plain text, or a byte that aborted a malformed
+                // escape sequence.
+                self.seq.clear();
+                if start.is_none() {
+                    start = Some(i);
+                }
+                continue;
+            }
+            if let Some(s) = start.take() {
+                emit(AnsiEvent::Text(&bytes[s..i]))?;
+            }
+            if prev == State::StringEscape && next != State::Ground {
+                // A string ended by the start of another escape sequence.
+                self.seq.pop();
+                self.dispatch(&mut emit)?;
+                self.seq.clear();
+                if b != 0x1B {
+                    self.seq.push(0x1B);
+                }
+            } else if next == State::Escape {
+                self.seq.clear();
+            }
+            if self.seq.len() < MAX_SEQUENCE_LEN {
+                self.seq.push(b);
+            }
+            if next == State::Ground {
+                self.dispatch(&mut emit)?;
+                self.seq.clear();
+            }
+        }
+        if let Some(s) = start {
+            emit(AnsiEvent::Text(&bytes[s..]))?;
+        }
+        Ok(())
+    }
+
+    /// Handle the complete escape sequence in `seq`.
+    fn dispatch<F>(&mut self, emit: &mut F) -> io::Result<()>
+    where
+        F: FnMut(AnsiEvent<'_>) -> io::Result<()>,
+    {
+        if self.seq.len() >= MAX_SEQUENCE_LEN {
+            return Ok(());
+        }
+        match self.seq.get(1) {
+            Some(b'[') => {
+                let (last, params) = match self.seq[2..].split_last() {
+                    None => return Ok(()),
+                    Some((&last, params)) => (last, params),
+                };
+                let is_sgr = last == b'm'
+                    && params.iter().all(|&b| {
+                        b.is_ascii_digit() || b == b';' || b == b':'
+                    });
+                if !is_sgr {
+                    return emit(AnsiEvent::Csi(&self.seq[2..]));
+                }
+                let mut style = self.style.clone();
+                apply_sgr(&mut style, params);
+                if style != self.style {
+                    self.style = style;
+                    emit(AnsiEvent::Style(&self.style))?;
+                }
+            }
+            Some(b']') => {
+                let payload = match self.seq[2..].strip_suffix(b"\x07") {
+                    Some(payload) => payload,
+                    None => self.seq[2..]
+                        .strip_suffix(b"\x1B\\")
+                        .unwrap_or(&self.seq[2..]),
+                };
+                // An OSC 8 sequence is `8;params;uri`, where an empty URI
+                // closes the hyperlink.
+                let rest = match payload.strip_prefix(b"8;") {
+                    None => return Ok(()),
+                    Some(rest) => rest,
+                };
+                let uri = match rest.iter().position(|&b| b == b';') {
+                    None => return Ok(()),
+                    Some(i) => &rest[i + 1..],
+                };
+                if uri.is_empty() {
+                    if self.hyperlink.take().is_some() {
+                        emit(AnsiEvent::Hyperlink(HyperlinkSpec::close()))?;
+                    }
+                } else {
+                    let uri = uri.to_vec();
+                    let hyperlink = self.hyperlink.insert(uri);
+                    emit(AnsiEvent::Hyperlink(HyperlinkSpec::open(
+                        hyperlink,
+                    )))?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Apply the parameters of an SGR sequence, e.g., `1;38;5;196`, to a style.
+fn apply_sgr(style: &mut ColorSpec, params: &[u8]) {
+    let params: Vec<&[u8]> = params.split(|&b| b == b';').collect();
+    let mut i = 0;
+    while i < params.len() {
+        let sub: Vec<Option<u32>> =
+            params[i].split(|&b| b == b':').map(parse_param).collect();
+        i += 1;
+        let code = match sub[0] {
+            None => continue,
+            Some(code) => code,
+        };
+        match code {
+            0 => style.clear(),
+            1 => {
+                style.set_bold(true);
+            }
+            2 => {
+                style.set_dimmed(true);
+            }
+            3 => {
+                style.set_italic(true);
+            }
+            4 => match sub.get(1) {
+                None => {
+                    style
+                        .set_underline(true)
+                        .set_underline_style(UnderlineStyle::Single);
+                }
+                Some(&Some(0)) => {
+                    style
+                        .set_underline(false)
+                        .set_underline_style(UnderlineStyle::Single);
+                }
+                Some(&Some(n)) => {
+                    if let Some(ul) = underline_style(n) {
+                        style.set_underline(true).set_underline_style(ul);
+                    }
+                }
+                Some(&None) => {}
+            },
+            5 | 6 => {
+                style.set_blink(true);
+            }
+            7 => {
+                style.set_reverse(true);
+            }
+            8 => {
+                style.set_hidden(true);
+            }
+            9 => {
+                style.set_strikethrough(true);
+            }
+            21 => {
+                style
+                    .set_underline(true)
+                    .set_underline_style(UnderlineStyle::Double);
+            }
+            22 => {
+                style.set_bold(false).set_dimmed(false);
+            }
+            23 => {
+                style.set_italic(false);
+            }
+            24 => {
+                style
+                    .set_underline(false)
+                    .set_underline_style(UnderlineStyle::Single);
+            }
+            25 => {
+                style.set_blink(false);
+            }
+            27 => {
+                style.set_reverse(false);
+            }
+            28 => {
+                style.set_hidden(false);
+            }
+            29 => {
+                style.set_strikethrough(false);
+            }
+            30..=37 => {
+                style.set_fg(Some(basic_color(code - 30)));
+            }
+            38 => {
+                let color = extended_color(&sub, &params, &mut i);
+                if color.is_some() {
+                    style.set_fg(color);
+                }
+            }
+            39 => {
+                style.set_fg(None);
+            }
+            40..=47 => {
+                style.set_bg(Some(basic_color(code - 40)));
+            }
+            48 => {
+                let color = extended_color(&sub, &params, &mut i);
+                if color.is_some() {
+                    style.set_bg(color);
+                }
+            }
+            49 => {
+                style.set_bg(None);
+            }
+            53 => {
+                style.set_overline(true);
+            }
+            55 => {
+                style.set_overline(false);
+            }
+            58 => {
+                let color = extended_color(&sub, &params, &mut i);
+                if color.is_some() {
+                    style.set_underline_color(color);
+                }
+            }
+            59 => {
+                style.set_underline_color(None);
+            }
+            90..=97 => {
+                style.set_fg(Some(basic_color(code - 90 + 8)));
+            }
+            100..=107 => {
+                style.set_bg(Some(basic_color(code - 100 + 8)));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parse a single SGR parameter. An empty parameter is zero.
+fn parse_param(param: &[u8]) -> Option<u32> {
+    if param.is_empty() {
+        return Some(0);
+    }
+    if param.len() > 9 || !param.iter().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    std::str::from_utf8(param).ok()?.parse().ok()
+}
+
+/// Returns one of the 16 system colors, as a named or bright color.
+fn basic_color(n: u32) -> Color {
+    match n {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        7 => Color::White,
+        8 => Color::BrightBlack,
+        9 => Color::BrightRed,
+        10 => Color::BrightGreen,
+        11 => Color::BrightYellow,
+        12 => Color::BrightBlue,
+        13 => Color::BrightMagenta,
+        14 => Color::BrightCyan,
+        _ => Color::BrightWhite,
+    }
+}
+
+fn underline_style(n: u32) -> Option<UnderlineStyle> {
+    match n {
+        1 => Some(UnderlineStyle::Single),
+        2 => Some(UnderlineStyle::Double),
+        3 => Some(UnderlineStyle::Curly),
+        4 => Some(UnderlineStyle::Dotted),
+        5 => Some(UnderlineStyle::Dashed),
+        _ => None,
+    }
+}
+
+/// Decode the color following a 38, 48 or 58 parameter.
+///
+/// `sub` is the parameter split on `:`. If it has sub-parameters, then the
+/// color is read from those, e.g., `38:2::255:0:0` or `38:5:196`. Otherwise
+/// the color is read from the parameters that follow, e.g., `38;2;255;0;0`,
+/// and `i` is advanced past them.
+fn extended_color(
+    sub: &[Option<u32>],
+    params: &[&[u8]],
+    i: &mut usize,
+) -> Option<Color> {
+    let byte = |n: Option<u32>| n.and_then(|n| u8::try_from(n).ok());
+    if sub.len() > 1 {
+        return match sub[1] {
+            Some(5) => byte(*sub.get(2)?).map(Color::Ansi256),
+            // The color space identifier between the 2 and the components
+            // is optional.
+            Some(2) => {
+                let rgb = if sub.len() >= 6 { &sub[3..6] } else { &sub[2..] };
+                match *rgb {
+                    [r, g, b] => {
+                        Some(Color::Rgb(byte(r)?, byte(g)?, byte(b)?))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+    }
+    let mut next = || {
+        let param = params.get(*i)?;
+        *i += 1;
+        Some(parse_param(param))
+    };
+    match next()? {
+        Some(5) => byte(next()?).map(Color::Ansi256),
+        Some(2) => {
+            let (r, g, b) = (next()?, next()?, next()?);
+            Some(Color::Rgb(byte(r)?, byte(g)?, byte(b)?))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnsiEvent, AnsiParser};
+    use crate::{Color, ColorSpec, UnderlineStyle};
+
+    /// An owned version of `AnsiEvent` for comparisons.
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Text(String),
+        Style(ColorSpec),
+        Hyperlink(Option<String>),
+        Csi(String),
+    }
+
+    fn parse_chunks(chunks: &[&[u8]]) -> Vec<Event> {
+        let mut parser = AnsiParser::new();
+        let mut events = vec![];
+        for chunk in chunks {
+            parser
+                .feed(chunk, |event| {
+                    events.push(match event {
+                        AnsiEvent::Text(t) => {
+                            Event::Text(String::from_utf8_lossy(t).into())
+                        }
+                        AnsiEvent::Style(s) => Event::Style(s.clone()),
+                        AnsiEvent::Hyperlink(h) => Event::Hyperlink(
+                            h.uri().map(|u| String::from_utf8_lossy(u).into()),
+                        ),
+                        AnsiEvent::Csi(c) => {
+                            Event::Csi(String::from_utf8_lossy(c).into())
+                        }
+                    });
+                    Ok(())
+                })
+                .unwrap();
+        }
+        // Merge adjacent text, which may be split at chunk boundaries.
+        let mut merged: Vec<Event> = vec![];
+        for event in events {
+            match (merged.last_mut(), event) {
+                (Some(Event::Text(a)), Event::Text(b)) => a.push_str(&b),
+                (_, event) => merged.push(event),
+            }
+        }
+        merged
+    }
+
+    fn parse(bytes: &[u8]) -> Vec<Event> {
+        parse_chunks(&[bytes])
+    }
+
+    fn style(s: &str) -> Event {
+        Event::Style(s.parse().unwrap())
+    }
+
+    fn text(s: &str) -> Event {
+        Event::Text(s.to_string())
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(
+            parse(b"a\x1B[31mb\x1B[0mc"),
+            vec![text("a"), style("fg:red"), text("b"), style(""), text("c")]
+        );
+        assert_eq!(
+            parse(b"\x1B[0;1;3;38;2;255;0;0;48;5;236mx"),
+            vec![style("fg:rgb(255,0,0) bg:236 bold italic"), text("x")]
+        );
+        assert_eq!(
+            parse(b"\x1B[38:2::1:2:3;48:5:9;58:2:4:5:6mx"),
+            vec![style("fg:rgb(1,2,3) bg:9 ul:rgb(4,5,6)"), text("x")]
+        );
+        assert_eq!(
+            parse(b"\x1B[92;104mx\x1B[39m"),
+            vec![
+                style("fg:bright-green bg:bright-blue"),
+                text("x"),
+                style("bg:bright-blue")
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        let mut spec = ColorSpec::new();
+        spec.set_underline(true).set_underline_style(UnderlineStyle::Curly);
+        assert_eq!(
+            parse(b"\x1B[1;2;5;7;8;9;53m\x1B[22;4:3m\x1B[25;27;28;29;55m"),
+            vec![
+                style("bold dimmed blink reverse hidden strikethrough overline"),
+                style("blink reverse hidden strikethrough overline underline:curly"),
+                Event::Style(spec),
+            ]
+        );
+        assert_eq!(
+            parse(b"\x1B[21mx\x1B[24m"),
+            vec![style("underline:double"), text("x"), style("")]
+        );
+    }
+
+    #[test]
+    fn incremental_styles() {
+        assert_eq!(
+            parse(b"\x1B[31m\x1B[1ma\x1B[1mb\x1B[m"),
+            vec![style("fg:red"), style("fg:red bold"), text("ab"), style(""),]
+        );
+    }
+
+    #[test]
+    fn hyperlinks() {
+        assert_eq!(
+            parse(b"\x1B]8;;https://a.b\x1B\\link\x1B]8;;\x1B\\"),
+            vec![
+                Event::Hyperlink(Some("https://a.b".into())),
+                text("link"),
+                Event::Hyperlink(None),
+            ]
+        );
+        assert_eq!(
+            parse(b"\x1B]8;id=1;file:///x\x07y\x1B]8;;\x07\x1B]0;title\x07"),
+            vec![
+                Event::Hyperlink(Some("file:///x".into())),
+                text("y"),
+                Event::Hyperlink(None),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_across_feeds() {
+        let input = b"\x1B[1;38;5;196mhot\x1B]8;;u\x1B\\k\x1B[0m";
+        let chunks: Vec<&[u8]> = input.chunks(1).collect();
+        assert_eq!(
+            parse_chunks(&chunks),
+            vec![
+                style("fg:196 bold"),
+                text("hot"),
+                Event::Hyperlink(Some("u".into())),
+                text("k"),
+                style(""),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        // Invalid colors are skipped along with their parameters.
+        assert_eq!(
+            parse(b"\x1B[38;5;300;1mx\x1B[38;2;1m"),
+            vec![style("bold"), text("x")]
+        );
+        // Unknown codes are ignored, and other control sequences are passed
+        // along undecoded.
+        assert_eq!(
+            parse(b"\x1B[73;3m\x1B[2J\x1B[?1m\x1B7"),
+            vec![
+                style("italic"),
+                Event::Csi("2J".into()),
+                Event::Csi("?1m".into()),
+            ]
+        );
+        // An interrupted sequence is dropped, but the text after it is kept.
+        assert_eq!(
+            parse(b"\x1B[31\x1B[32mx\x1B[1\ny"),
+            vec![style("fg:green"), text("x\ny")]
+        );
+        // Invalid UTF-8 is passed through.
+        let mut parser = AnsiParser::new();
+        let mut out = vec![];
+        parser
+            .feed(b"\xFF\x1B[1m\xC3", |event| {
+                if let AnsiEvent::Text(t) = event {
+                    out.extend_from_slice(t);
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(out, b"\xFF\xC3");
+        assert!(parser.style().bold());
+        assert_eq!(parser.style().fg(), None::<&Color>);
+    }
+}
@@ -0,0 +1,243 @@
+use std::io;
+
+use crate::{ColorSpec, HyperlinkSpec, WriteColor};
+
+/// A piece of text recorded by a [`Recorder`], along with its style and
+/// hyperlink.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Segment {
+    spec: ColorSpec,
+    hyperlink: Option<Vec<u8>>,
+    text: Vec<u8>,
+}
+
+impl Segment {
+    /// Get the effective style of the text.
+    ///
+    /// This is the style that results from all calls to `set_color` and
+    /// `reset` before the text was written, so it always resets.
+    pub fn spec(&self) -> &ColorSpec {
+        &self.spec
+    }
+
+    /// Get the URI of the hyperlink active when the text was written, if any.
+    pub fn hyperlink(&self) -> Option<&[u8]> {
+        self.hyperlink.as_deref()
+    }
+
+    /// Get the text.
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+}
+
+/// Satisfies `WriteColor` by recording text along with its style, which is
+/// useful to test colored output without comparing escape sequences.
+///
+/// Every write is recorded as a [`Segment`] holding the effective
+/// `ColorSpec`, the active hyperlink and the text. Segments that are next to
+/// each other and have the same style and hyperlink can be merged with
+/// [`Recorder::merge`], and [`Recorder::render`] returns a readable form of
+/// the recording.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Write;
+/// use termcolor2::{Color, ColorSpec, Recorder, WriteColor};
+///
+/// let mut rec = Recorder::new();
+/// rec.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))?;
+/// write!(rec, "error")?;
+/// rec.reset()?;
+/// write!(rec, ": oops")?;
+/// assert_eq!(rec.render(), "[fg:red bold]error[/]: oops");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Recorder {
+    segments: Vec<Segment>,
+    spec: ColorSpec,
+    hyperlink: Option<Vec<u8>>,
+}
+
+impl Recorder {
+    /// Create a new, empty recorder.
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+
+    /// Return the segments recorded so far.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Consume this recorder and return the segments recorded.
+    pub fn into_segments(self) -> Vec<Segment> {
+        self.segments
+    }
+
+    /// Clear the segments recorded so far.
+    ///
+    /// The current style and hyperlink are kept.
+    pub fn clear(&mut self) {
+        self.segments.clear();
+    }
+
+    /// Merge adjacent segments with the same style and hyperlink into one.
+    pub fn merge(&mut self) {
+        let mut merged: Vec<Segment> = Vec::with_capacity(self.segments.len());
+        for seg in self.segments.drain(..) {
+            match merged.last_mut() {
+                Some(last)
+                    if last.spec == seg.spec
+                        && last.hyperlink == seg.hyperlink =>
+                {
+                    last.text.extend_from_slice(&seg.text);
+                }
+                _ => merged.push(seg),
+            }
+        }
+        self.segments = merged;
+    }
+
+    /// Returns a readable form of the recorded text, with adjacent segments
+    /// merged.
+    ///
+    /// Text without a style or hyperlink is written as is. Other text is
+    /// wrapped in tags, where the opening tag is the textual form of its
+    /// `ColorSpec`, followed by `link=URI` if it has a hyperlink, e.g.,
+    /// `[fg:red bold]error[/]` or `[underline link=https://x.y]x.y[/]`.
+    /// Invalid UTF-8 is replaced with `U+FFFD`.
+    pub fn render(&self) -> String {
+        let mut rec = self.clone();
+        rec.merge();
+        let mut out = String::new();
+        for seg in &rec.segments {
+            let text = String::from_utf8_lossy(&seg.text);
+            if seg.spec.is_none() && seg.hyperlink.is_none() {
+                out.push_str(&text);
+                continue;
+            }
+            let mut tag = seg.spec.to_string();
+            if let Some(ref uri) = seg.hyperlink {
+                if !tag.is_empty() {
+                    tag.push(' ');
+                }
+                tag.push_str("link=");
+                tag.push_str(&String::from_utf8_lossy(uri));
+            }
+            out.push('[');
+            out.push_str(&tag);
+            out.push(']');
+            out.push_str(&text);
+            out.push_str("[/]");
+        }
+        out
+    }
+}
+
+impl io::Write for Recorder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !buf.is_empty() {
+            self.segments.push(Segment {
+                spec: self.spec.clone(),
+                hyperlink: self.hyperlink.clone(),
+                text: buf.to_vec(),
+            });
+        }
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl WriteColor for Recorder {
+    #[inline]
+    fn supports_color(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn supports_hyperlinks(&self) -> bool {
+        true
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.spec = self.spec.apply(spec);
+        Ok(())
+    }
+
+    fn set_hyperlink(&mut self, link: &HyperlinkSpec) -> io::Result<()> {
+        self.hyperlink = link.uri().map(|uri| uri.to_vec());
+        Ok(())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.spec.clear();
+        Ok(())
+    }
+
+    #[inline]
+    fn is_synchronous(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::Recorder;
+    use crate::tests::spec;
+    use crate::{HyperlinkSpec, WriteColor};
+
+    #[test]
+    fn segments() {
+        let mut rec = Recorder::new();
+        rec.set_color(&spec("fg:red")).unwrap();
+        write!(rec, "a").unwrap();
+        rec.set_color(&spec("bold noreset")).unwrap();
+        write!(rec, "b").unwrap();
+        write!(rec, "c").unwrap();
+        rec.reset().unwrap();
+        write!(rec, "d").unwrap();
+
+        let segs = rec.segments();
+        assert_eq!(segs.len(), 4);
+        assert_eq!(segs[0].spec(), &spec("fg:red"));
+        assert_eq!(segs[1].spec(), &spec("fg:red bold"));
+        assert_eq!(segs[1].text(), b"b");
+        assert!(segs[3].spec().is_none());
+
+        rec.merge();
+        let texts: Vec<&[u8]> =
+            rec.segments().iter().map(|s| s.text()).collect();
+        assert_eq!(texts, vec![&b"a"[..], b"bc", b"d"]);
+    }
+
+    #[test]
+    fn render() {
+        let mut rec = Recorder::new();
+        write!(rec, "see ").unwrap();
+        rec.set_hyperlink(&HyperlinkSpec::open(b"https://x.y")).unwrap();
+        rec.set_color(&spec("underline")).unwrap();
+        write!(rec, "x").unwrap();
+        write!(rec, ".y").unwrap();
+        rec.reset().unwrap();
+        write!(rec, "!").unwrap();
+        rec.set_hyperlink(&HyperlinkSpec::close()).unwrap();
+        rec.set_color(&spec("fg:green bg:rgb(1,2,3) italic")).unwrap();
+        rec.write_all(b"ok\xFF").unwrap();
+        assert_eq!(
+            rec.render(),
+            "see [underline link=https://x.y]x.y[/][link=https://x.y]![/]\
+             [fg:green bg:rgb(1,2,3) italic]ok\u{FFFD}[/]"
+        );
+        // Rendering doesn't merge the recorded segments.
+        assert_eq!(rec.segments().len(), 5);
+    }
+}
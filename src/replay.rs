@@ -0,0 +1,149 @@
+use std::io;
+
+use crate::{AnsiEvent, AnsiParser, ColorSpec, HyperlinkSpec, WriteColor};
+
+/// Satisfies `WriteColor` by replaying ANSI escape sequences in the text
+/// written to it onto another `WriteColor`.
+///
+/// Text written to an `AnsiReplay` is parsed with an [`AnsiParser`]. Styles
+/// and hyperlinks in it are translated into calls to `set_color`, `reset` and
+/// `set_hyperlink` on the wrapped writer, and all other escape sequences, such
+/// as cursor movements, are removed. This makes it possible to embed the
+/// colored output of another program in the output of any writer in this
+/// crate. For example, the output keeps its colors when written to a Windows
+/// console, and loses them cleanly when written to a `NoColor` writer.
+///
+/// Calling the `WriteColor` methods of an `AnsiReplay` directly forwards them
+/// to the wrapped writer.
+#[derive(Clone, Debug)]
+pub struct AnsiReplay<W> {
+    wtr: W,
+    parser: AnsiParser,
+}
+
+impl<W: WriteColor> AnsiReplay<W> {
+    /// Create a new writer that replays ANSI escape sequences onto the given
+    /// writer.
+    pub fn new(wtr: W) -> AnsiReplay<W> {
+        AnsiReplay { wtr, parser: AnsiParser::new() }
+    }
+
+    /// Consume this `AnsiReplay` value and return the inner writer.
+    ///
+    /// An escape sequence that is still incomplete is discarded.
+    pub fn into_inner(self) -> W {
+        self.wtr
+    }
+
+    /// Return a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.wtr
+    }
+
+    /// Return a mutable reference to the inner writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.wtr
+    }
+}
+
+impl<W: WriteColor> io::Write for AnsiReplay<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let AnsiReplay { ref mut wtr, ref mut parser } = *self;
+        parser.feed(buf, |event| match event {
+            AnsiEvent::Text(text) => wtr.write_all(text),
+            AnsiEvent::Style(spec) if spec.is_none() => wtr.reset(),
+            AnsiEvent::Style(spec) => wtr.set_color(spec),
+            AnsiEvent::Hyperlink(link) => wtr.set_hyperlink(&link),
+            _ => Ok(()),
+        })?;
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for AnsiReplay<W> {
+    #[inline]
+    fn supports_color(&self) -> bool {
+        self.wtr.supports_color()
+    }
+
+    #[inline]
+    fn supports_hyperlinks(&self) -> bool {
+        self.wtr.supports_hyperlinks()
+    }
+
+    #[inline]
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.wtr.set_color(spec)
+    }
+
+    #[inline]
+    fn set_hyperlink(&mut self, link: &HyperlinkSpec) -> io::Result<()> {
+        self.wtr.set_hyperlink(link)
+    }
+
+    #[inline]
+    fn reset(&mut self) -> io::Result<()> {
+        self.wtr.reset()
+    }
+
+    #[inline]
+    fn is_synchronous(&self) -> bool {
+        self.wtr.is_synchronous()
+    }
+
+    #[inline]
+    fn is_ansi(&self) -> bool {
+        self.wtr.is_ansi()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::AnsiReplay;
+    use crate::{Ansi, ColorLevel, NoColor};
+
+    #[test]
+    fn replay_onto_ansi() {
+        let mut wtr = AnsiReplay::new(Ansi::new(vec![]));
+        wtr.write_all(b"\x1B[1m\x1B[31mbold red\x1B[22m red\x1B[0m plain")
+            .unwrap();
+        assert_eq!(
+            wtr.into_inner().into_inner(),
+            b"\x1B[0;1m\x1B[0;1;31mbold red\x1B[0;31m red\x1B[0m plain"
+        );
+
+        // Colors are written at the color level of the wrapped writer.
+        let inner = Ansi::with_color_level(vec![], ColorLevel::Ansi256);
+        let mut wtr = AnsiReplay::new(inner);
+        wtr.write_all(b"\x1B[38;2;255;0;0mx\x1B[2Ky").unwrap();
+        assert_eq!(wtr.into_inner().into_inner(), b"\x1B[0;38;5;196mxy");
+    }
+
+    #[test]
+    fn replay_onto_no_color() {
+        let mut wtr = AnsiReplay::new(NoColor::new(vec![]));
+        wtr.write_all(b"\x1B]8;;u\x1B\\\x1B[31mred\x1B[0m\x1B]8;;\x1B\\!")
+            .unwrap();
+        assert_eq!(wtr.into_inner().into_inner(), b"red!");
+    }
+
+    #[test]
+    fn split_writes() {
+        let input = b"a\x1B]8;;u\x07\x1B[4mb\x1B[m\x1B]8;;\x07";
+        let mut wtr = AnsiReplay::new(Ansi::new(vec![]));
+        for chunk in input.chunks(1) {
+            wtr.write_all(chunk).unwrap();
+        }
+        assert_eq!(
+            wtr.into_inner().into_inner(),
+            b"a\x1B]8;;u\x1B\\\x1B[0;4mb\x1B[0m\x1B]8;;\x1B\\"
+        );
+    }
+}
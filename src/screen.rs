@@ -0,0 +1,594 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+use crate::styled::char_width;
+use crate::{AnsiEvent, AnsiParser, ColorSpec, Recorder, WriteColor};
+
+/// The number of columns between tab stops.
+const TAB_WIDTH: usize = 8;
+
+/// A single character cell of a [`Screen`].
+///
+/// A wide character takes up two cells. The first one holds the character,
+/// and the second one is empty.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cell {
+    /// The character, followed by any zero width characters written after
+    /// it. This is empty for the second cell of a wide character.
+    text: String,
+    spec: ColorSpec,
+}
+
+impl Default for Cell {
+    fn default() -> Cell {
+        Cell { text: " ".to_string(), spec: ColorSpec::new() }
+    }
+}
+
+impl Cell {
+    /// Get the character in this cell, without any zero width characters
+    /// that follow it. Blank cells and the second cell of a wide character
+    /// contain a space.
+    pub fn ch(&self) -> char {
+        self.text.chars().next().unwrap_or(' ')
+    }
+
+    /// Get the text in this cell, i.e., the character followed by any zero
+    /// width characters, such as combining marks, written after it.
+    ///
+    /// This is empty for the second cell of a wide character.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Get the style of this cell.
+    pub fn spec(&self) -> &ColorSpec {
+        &self.spec
+    }
+}
+
+/// A virtual terminal screen, which shows what a user would see after the
+/// bytes written to it are displayed by a terminal.
+///
+/// A `Screen` consumes the text and escape sequences written by `Ansi`. It
+/// keeps a grid of [`Cell`]s with a fixed width and height, each holding a
+/// character and its style. To write colored text to a screen, wrap it in an
+/// `Ansi` writer.
+///
+/// The following are supported:
+///
+/// * Text, which wraps to the next line at the right edge of the screen.
+///   Most characters take up one cell, while wide characters, such as CJK
+///   ideographs and most emoji, take up two. Zero width characters, such as
+///   combining marks, are added to the cell of the character before them.
+/// * New lines, which also move the cursor to the start of the line, as a
+///   terminal does for programs writing to it.
+/// * Carriage returns, backspaces and tabs.
+/// * SGR sequences setting colors and attributes.
+/// * Cursor movement (`CSI A`, `B`, `C`, `D`, `E`, `F`, `G`, `H` and `f`),
+///   erasing the screen (`CSI J`) and erasing the line (`CSI K`).
+///
+/// All other escape sequences and control characters are ignored, and
+/// invalid UTF-8 is replaced with `U+FFFD`.
+///
+/// Lines that scroll off the top of the screen are kept in the scrollback,
+/// up to a configurable limit.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Write;
+/// use termcolor2::{Ansi, Color, ColorSpec, Screen, WriteColor};
+///
+/// let mut wtr = Ansi::new(Screen::new(20, 3));
+/// write!(wtr, "progress: 10%\rprogress: 100%\r")?;
+/// wtr.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+/// write!(wtr, "done")?;
+/// wtr.reset()?;
+///
+/// let screen = wtr.into_inner();
+/// assert_eq!(screen.text(), "doneress: 100%");
+/// assert_eq!(screen.dump(), "[fg:green]done[/]ress: 100%");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Screen {
+    width: usize,
+    height: usize,
+    grid: Vec<Vec<Cell>>,
+    scrollback: VecDeque<Vec<Cell>>,
+    scrollback_limit: usize,
+    row: usize,
+    col: usize,
+    /// Set when a character was written in the last column. The cursor then
+    /// stays in that column until the next character, which is written on
+    /// the next line.
+    wrap_pending: bool,
+    parser: AnsiParser,
+    /// The style of the text written now.
+    style: ColorSpec,
+    /// The bytes of an incomplete UTF-8 encoded character.
+    partial: Vec<u8>,
+}
+
+impl Screen {
+    /// Create a new blank screen with the given number of columns and rows,
+    /// and a scrollback limit of 1000 lines.
+    ///
+    /// The screen is at least one column wide and one row high.
+    pub fn new(width: usize, height: usize) -> Screen {
+        let (width, height) = (width.max(1), height.max(1));
+        Screen {
+            width,
+            height,
+            grid: vec![vec![Cell::default(); width]; height],
+            scrollback: VecDeque::new(),
+            scrollback_limit: 1000,
+            row: 0,
+            col: 0,
+            wrap_pending: false,
+            parser: AnsiParser::new(),
+            style: ColorSpec::new(),
+            partial: vec![],
+        }
+    }
+
+    /// Get the number of columns.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the number of rows.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the position of the cursor, as a zero based `(row, column)`.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    /// Get the cell at the given zero based row and column of the screen.
+    pub fn cell(&self, row: usize, col: usize) -> Option<&Cell> {
+        self.grid.get(row)?.get(col)
+    }
+
+    /// Get the cells of the given zero based row of the screen.
+    pub fn row(&self, row: usize) -> Option<&[Cell]> {
+        self.grid.get(row).map(|r| &r[..])
+    }
+
+    /// Return the rows that scrolled off the top of the screen, oldest
+    /// first.
+    pub fn scrollback(&self) -> impl Iterator<Item = &[Cell]> + '_ {
+        self.scrollback.iter().map(|r| &r[..])
+    }
+
+    /// Get the maximum number of rows kept in the scrollback.
+    pub fn scrollback_limit(&self) -> usize {
+        self.scrollback_limit
+    }
+
+    /// Set the maximum number of rows kept in the scrollback. The oldest
+    /// rows are dropped first.
+    pub fn set_scrollback_limit(&mut self, limit: usize) {
+        self.scrollback_limit = limit;
+        self.trim_scrollback();
+    }
+
+    /// Returns the text of the scrollback followed by the screen, without
+    /// styles.
+    ///
+    /// Each row is a line, with trailing blank cells removed. Trailing empty
+    /// lines are removed as well.
+    pub fn text(&self) -> String {
+        self.lines(|row| {
+            let line: String = row.iter().map(|c| c.text()).collect();
+            line.trim_end_matches(' ').to_string()
+        })
+    }
+
+    /// Returns the text of the scrollback followed by the screen, annotated
+    /// with styles.
+    ///
+    /// This is the same as [`Screen::text`], except that styled text is
+    /// wrapped in tags in the same form as [`Recorder::render`], e.g.,
+    /// `[fg:red bold]error[/]`. This makes it suitable for golden file tests.
+    pub fn dump(&self) -> String {
+        self.lines(|row| {
+            let end = row
+                .iter()
+                .rposition(|c| c.text != " " || !c.spec.is_none())
+                .map_or(0, |i| i + 1);
+            let mut rec = Recorder::new();
+            for cell in &row[..end] {
+                // Writing to a `Recorder` never fails.
+                let _ = rec.set_color(&cell.spec);
+                let _ = rec.write_all(cell.text.as_bytes());
+            }
+            rec.render()
+        })
+    }
+
+    fn lines<F: Fn(&[Cell]) -> String>(&self, render: F) -> String {
+        let mut lines: Vec<String> = self
+            .scrollback()
+            .chain(self.grid.iter().map(|r| &r[..]))
+            .map(render)
+            .collect();
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        lines.join("\n")
+    }
+
+    fn trim_scrollback(&mut self) {
+        while self.scrollback.len() > self.scrollback_limit {
+            self.scrollback.pop_front();
+        }
+    }
+
+    /// Move the cursor down one row, scrolling the screen if it is on the
+    /// last one.
+    fn line_feed(&mut self) {
+        self.wrap_pending = false;
+        if self.row + 1 < self.height {
+            self.row += 1;
+            return;
+        }
+        let top = self.grid.remove(0);
+        self.grid.push(vec![Cell::default(); self.width]);
+        if self.scrollback_limit > 0 {
+            self.scrollback.push_back(top);
+            self.trim_scrollback();
+        }
+    }
+
+    fn put(&mut self, c: char) {
+        match c {
+            '\n' => {
+                self.line_feed();
+                self.col = 0;
+            }
+            '\r' => {
+                self.col = 0;
+                self.wrap_pending = false;
+            }
+            '\x08' => {
+                self.col = self.col.saturating_sub(1);
+                self.wrap_pending = false;
+            }
+            '\t' => {
+                let stop = (self.col / TAB_WIDTH + 1) * TAB_WIDTH;
+                self.col = stop.min(self.width - 1);
+            }
+            c if c.is_control() => {}
+            c if char_width(c) == 0 => self.put_zero_width(c),
+            c => {
+                // A wide character that doesn't fit at the end of the line
+                // is written on the next one. On a screen one column wide,
+                // it takes up a single cell instead.
+                let width = char_width(c).min(self.width);
+                if self.wrap_pending || self.col + width > self.width {
+                    self.line_feed();
+                    self.col = 0;
+                }
+                let spec = self.style.clone();
+                self.clear_wide(self.row, self.col);
+                self.grid[self.row][self.col] =
+                    Cell { text: c.to_string(), spec: spec.clone() };
+                if width == 2 {
+                    self.clear_wide(self.row, self.col + 1);
+                    self.grid[self.row][self.col + 1] =
+                        Cell { text: String::new(), spec };
+                }
+                if self.col + width < self.width {
+                    self.col += width;
+                } else {
+                    self.col = self.width - 1;
+                    self.wrap_pending = true;
+                }
+            }
+        }
+    }
+
+    /// Add a zero width character to the cell of the character written
+    /// before the cursor. It is dropped if there is no such cell.
+    fn put_zero_width(&mut self, c: char) {
+        let mut col = if self.wrap_pending {
+            self.col
+        } else if self.col > 0 {
+            self.col - 1
+        } else {
+            return;
+        };
+        let row = &mut self.grid[self.row];
+        if row[col].text.is_empty() && col > 0 {
+            col -= 1;
+        }
+        row[col].text.push(c);
+    }
+
+    /// Blank the other half of a wide character that is about to be
+    /// partially overwritten at the given cell.
+    fn clear_wide(&mut self, row: usize, col: usize) {
+        let row = &mut self.grid[row];
+        if row[col].text.is_empty() && col > 0 {
+            row[col - 1] = Cell::default();
+        }
+        if row.get(col + 1).is_some_and(|c| c.text.is_empty()) {
+            row[col + 1] = Cell::default();
+        }
+    }
+
+    fn write_text(&mut self, text: &[u8]) {
+        self.partial.extend_from_slice(text);
+        let bytes = std::mem::take(&mut self.partial);
+        let mut rest = &bytes[..];
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(s) => {
+                    s.chars().for_each(|c| self.put(c));
+                    break;
+                }
+                Err(err) => {
+                    let (valid, after) = rest.split_at(err.valid_up_to());
+                    // The prefix was just validated.
+                    let valid = std::str::from_utf8(valid).unwrap_or("");
+                    valid.chars().for_each(|c| self.put(c));
+                    match err.error_len() {
+                        // An incomplete character, which may be completed by
+                        // the next write.
+                        None => {
+                            self.partial = after.to_vec();
+                            break;
+                        }
+                        Some(len) => {
+                            self.put('\u{FFFD}');
+                            rest = &after[len..];
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handle a control sequence other than SGR.
+    fn control(&mut self, seq: &[u8]) {
+        let (action, params) = match seq.split_last() {
+            None => return,
+            Some((&action, params)) => (action, params),
+        };
+        if !params.iter().all(|&b| b.is_ascii_digit() || b == b';') {
+            return;
+        }
+        let params: Vec<usize> = params
+            .split(|&b| b == b';')
+            .map(|p| {
+                std::str::from_utf8(p)
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or(0)
+            })
+            .collect();
+        let param = |i: usize| params.get(i).copied().unwrap_or(0);
+        // Movements default to, and are at least, one.
+        let count = param(0).max(1);
+        let (max_row, max_col) = (self.height - 1, self.width - 1);
+        self.wrap_pending = false;
+        match action {
+            b'A' => self.row = self.row.saturating_sub(count),
+            b'B' => self.row = self.row.saturating_add(count).min(max_row),
+            b'C' => self.col = self.col.saturating_add(count).min(max_col),
+            b'D' => self.col = self.col.saturating_sub(count),
+            b'E' => {
+                self.row = self.row.saturating_add(count).min(max_row);
+                self.col = 0;
+            }
+            b'F' => {
+                self.row = self.row.saturating_sub(count);
+                self.col = 0;
+            }
+            b'G' => self.col = (count - 1).min(max_col),
+            b'H' | b'f' => {
+                self.row = (param(0).max(1) - 1).min(max_row);
+                self.col = (param(1).max(1) - 1).min(max_col);
+            }
+            b'J' => {
+                let (row, col) = (self.row, self.col);
+                match param(0) {
+                    0 => {
+                        self.erase(row, col..self.width);
+                        for r in row + 1..self.height {
+                            self.erase(r, 0..self.width);
+                        }
+                    }
+                    1 => {
+                        for r in 0..row {
+                            self.erase(r, 0..self.width);
+                        }
+                        self.erase(row, 0..col + 1);
+                    }
+                    _ => {
+                        for r in 0..self.height {
+                            self.erase(r, 0..self.width);
+                        }
+                    }
+                }
+            }
+            b'K' => {
+                let (row, col) = (self.row, self.col);
+                match param(0) {
+                    0 => self.erase(row, col..self.width),
+                    1 => self.erase(row, 0..col + 1),
+                    _ => self.erase(row, 0..self.width),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn erase(&mut self, row: usize, cols: std::ops::Range<usize>) {
+        if cols.is_empty() {
+            return;
+        }
+        self.clear_wide(row, cols.start);
+        self.clear_wide(row, cols.end - 1);
+        for cell in &mut self.grid[row][cols] {
+            *cell = Cell::default();
+        }
+    }
+}
+
+impl io::Write for Screen {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The parser is taken out of the screen while feeding it, so that
+        // the events can be applied to the rest of the screen.
+        let mut parser = std::mem::take(&mut self.parser);
+        let result = parser.feed(buf, |event| {
+            match event {
+                AnsiEvent::Text(text) => self.write_text(text),
+                AnsiEvent::Style(spec) => self.style = spec.clone(),
+                AnsiEvent::Csi(seq) => self.control(seq),
+                _ => {}
+            }
+            Ok(())
+        });
+        self.parser = parser;
+        result?;
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::Screen;
+    use crate::{Ansi, Color, ColorSpec, WriteColor};
+
+    fn screen(width: usize, height: usize, bytes: &[u8]) -> Screen {
+        let mut screen = Screen::new(width, height);
+        screen.write_all(bytes).unwrap();
+        screen
+    }
+
+    #[test]
+    fn overwrites() {
+        assert_eq!(screen(10, 2, b"abc\x08\x08X").text(), "aXc");
+        assert_eq!(screen(10, 2, b"12345\rab\tc").text(), "ab345   c");
+        assert_eq!(screen(10, 2, b"a\x07\x00b").text(), "ab");
+    }
+
+    #[test]
+    fn wrapping() {
+        let s = screen(4, 3, b"abcdefg");
+        assert_eq!(s.text(), "abcd\nefg");
+        assert_eq!(s.cursor(), (1, 3));
+        // A new line right after the last column doesn't add an empty line.
+        assert_eq!(screen(4, 3, b"abcd\nx").text(), "abcd\nx");
+        let s = screen(4, 3, b"abcd");
+        assert_eq!(s.cursor(), (0, 3));
+    }
+
+    #[test]
+    fn scrollback() {
+        let mut s = screen(4, 2, b"1\n2\n3\n4");
+        assert_eq!(s.scrollback().count(), 2);
+        assert_eq!(s.row(0).unwrap()[0].ch(), '3');
+        assert_eq!(s.text(), "1\n2\n3\n4");
+        s.set_scrollback_limit(1);
+        assert_eq!(s.text(), "2\n3\n4");
+        s.set_scrollback_limit(0);
+        s.write_all(b"\n5").unwrap();
+        assert_eq!(s.text(), "4\n5");
+    }
+
+    #[test]
+    fn cursor_movement_and_erasing() {
+        let mut s = screen(10, 3, b"hello\nworld");
+        s.write_all(b"\x1B[1;1HJ").unwrap();
+        assert_eq!(s.text(), "Jello\nworld");
+        s.write_all(b"\x1B[2;3H\x1B[K").unwrap();
+        assert_eq!(s.text(), "Jello\nwo");
+        s.write_all(b"\x1B[A\x1B[2C!\x1B[99G?").unwrap();
+        assert_eq!(s.text(), "Jell!    ?\nwo");
+        s.write_all(b"\x1B[E\x1B[2C\x1B[1K").unwrap();
+        assert_eq!(s.text(), "Jell!    ?");
+        s.write_all(b"\x1B[2J").unwrap();
+        assert_eq!(s.text(), "");
+        assert_eq!(s.cursor(), (1, 2));
+    }
+
+    #[test]
+    fn styles() {
+        let mut wtr = Ansi::new(Screen::new(10, 3));
+        wtr.set_color(ColorSpec::new().set_fg(Some(Color::Red))).unwrap();
+        write!(wtr, "ab").unwrap();
+        wtr.reset().unwrap();
+        writeln!(wtr, "c").unwrap();
+        wtr.set_color(
+            ColorSpec::new().set_fg(Some(Color::Blue)).set_bold(true),
+        )
+        .unwrap();
+        write!(wtr, "d").unwrap();
+        wtr.set_color(ColorSpec::new().set_bg(Some(Color::Green))).unwrap();
+        write!(wtr, "  ").unwrap();
+        wtr.reset().unwrap();
+
+        let s = wtr.into_inner();
+        assert_eq!(s.cell(0, 0).unwrap().spec().fg(), Some(&Color::Red));
+        assert!(s.cell(0, 2).unwrap().spec().is_none());
+        assert!(s.cell(1, 0).unwrap().spec().bold());
+        assert_eq!(s.text(), "abc\nd");
+        // Blank cells with a style are kept.
+        assert_eq!(
+            s.dump(),
+            "[fg:red]ab[/]c\n[fg:blue bold]d[/][bg:green]  [/]"
+        );
+    }
+
+    #[test]
+    fn wide_and_zero_width_characters() {
+        let s = screen(5, 3, "日本語".as_bytes());
+        assert_eq!(s.text(), "日本\n語");
+        assert_eq!(s.cursor(), (1, 2));
+        assert_eq!(s.cell(0, 0).unwrap().text(), "日");
+        assert_eq!(s.cell(0, 1).unwrap().text(), "");
+        assert_eq!(s.cell(0, 4).unwrap().text(), " ");
+
+        // Zero width characters are attached to the character before them,
+        // including the first half of a wide character.
+        let s = screen(3, 2, "e\u{301}本\u{301}x\u{301}".as_bytes());
+        assert_eq!(s.text(), "e\u{301}本\u{301}\nx\u{301}");
+        assert_eq!(s.cell(0, 0).unwrap().ch(), 'e');
+
+        // Overwriting half of a wide character blanks the other half.
+        let mut s = screen(6, 2, "本日".as_bytes());
+        s.write_all(b"\x1B[2Gx\x1B[4G\x1B[K").unwrap();
+        assert_eq!(s.text(), " x");
+    }
+
+    #[test]
+    fn huge_parameters() {
+        let max = usize::MAX;
+        for action in ["A", "B", "C", "D", "E", "F", "G", "H", "J", "K"] {
+            let seq = format!("a\x1B[{max};{max}{action}b");
+            screen(10, 3, seq.as_bytes());
+        }
+        let s = screen(10, 3, format!("a\x1B[{max}C\x1B[{max}Bb").as_bytes());
+        assert_eq!(s.text(), "a\n\n         b");
+    }
+
+    #[test]
+    fn utf8() {
+        let mut s = screen(10, 2, b"\xC3");
+        s.write_all(b"\xA9\xFFx").unwrap();
+        assert_eq!(s.text(), "\u{e9}\u{FFFD}x");
+    }
+}
@@ -0,0 +1,207 @@
+use std::io;
+
+use crate::{ColorSpec, HyperlinkSpec, WriteColor};
+
+/// Satisfies `WriteColor` by keeping a stack of nested styles on top of
+/// another `WriteColor`.
+///
+/// [`StyleStack::push`] layers a color specification over the current style,
+/// using the semantics of [`ColorSpec::merged`]: colors it leaves unset are
+/// inherited from the current style, and attributes are added to it. Whether
+/// the pushed spec resets is ignored. [`StyleStack::pop`] restores the style
+/// that was current before the matching push.
+///
+/// Colors set with `set_color` are applied on top of the current layer in
+/// the same way, and `reset` restores the current layer's style instead of
+/// clearing it. This way, code that resets after writing styled text, e.g.,
+/// a [`StyledString`](crate::StyledString), doesn't undo the styles of the
+/// layers around it.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Write;
+/// use termcolor2::{ColorSpec, Recorder, StyleStack};
+///
+/// let mut stack = StyleStack::new(Recorder::new());
+/// stack.push(&"fg:red".parse::<ColorSpec>()?)?;
+/// write!(stack, "error: cannot open ")?;
+/// stack.push(&"italic".parse()?)?;
+/// write!(stack, "foo.txt")?;
+/// stack.pop()?;
+/// write!(stack, "!")?;
+/// stack.pop()?;
+/// assert_eq!(
+///     stack.into_inner().render(),
+///     "[fg:red]error: cannot open [/][fg:red italic]foo.txt[/][fg:red]![/]",
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct StyleStack<W> {
+    wtr: W,
+    /// The effective style of each layer. The first layer has no style and
+    /// is never popped.
+    layers: Vec<ColorSpec>,
+}
+
+impl<W: WriteColor> StyleStack<W> {
+    /// Create a new style stack that writes to the given writer, with no
+    /// layers.
+    pub fn new(wtr: W) -> StyleStack<W> {
+        StyleStack { wtr, layers: vec![ColorSpec::new()] }
+    }
+
+    /// Consume this stack and return the underlying writer.
+    ///
+    /// The style of the underlying writer isn't changed.
+    pub fn into_inner(self) -> W {
+        self.wtr
+    }
+
+    /// Return a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.wtr
+    }
+
+    /// Return a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.wtr
+    }
+
+    /// Returns the number of layers pushed and not yet popped.
+    pub fn depth(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    /// Returns the effective style of the current layer.
+    pub fn current(&self) -> &ColorSpec {
+        self.top()
+    }
+
+    /// Layer the given color specification over the current style and set
+    /// the result on the underlying writer.
+    pub fn push(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        let next = self.top().layered(spec);
+        self.apply(&next)?;
+        self.layers.push(next);
+        Ok(())
+    }
+
+    /// Remove the current layer and restore the style of the one below it.
+    ///
+    /// If no layers have been pushed, this does nothing.
+    pub fn pop(&mut self) -> io::Result<()> {
+        if self.layers.len() == 1 {
+            return Ok(());
+        }
+        self.layers.pop();
+        let top = self.top().clone();
+        self.apply(&top)
+    }
+
+    fn top(&self) -> &ColorSpec {
+        self.layers.last().expect("the first layer is never popped")
+    }
+
+    fn apply(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        if spec.is_none() {
+            self.wtr.reset()
+        } else {
+            self.wtr.set_color(spec)
+        }
+    }
+}
+
+impl<W: WriteColor> io::Write for StyleStack<W> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.wtr.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for StyleStack<W> {
+    #[inline]
+    fn supports_color(&self) -> bool {
+        self.wtr.supports_color()
+    }
+
+    #[inline]
+    fn supports_hyperlinks(&self) -> bool {
+        self.wtr.supports_hyperlinks()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        let next = self.top().layered(spec);
+        self.apply(&next)
+    }
+
+    #[inline]
+    fn set_hyperlink(&mut self, link: &HyperlinkSpec) -> io::Result<()> {
+        self.wtr.set_hyperlink(link)
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        let top = self.top().clone();
+        self.apply(&top)
+    }
+
+    #[inline]
+    fn is_synchronous(&self) -> bool {
+        self.wtr.is_synchronous()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::StyleStack;
+    use crate::tests::spec;
+    use crate::{Ansi, Recorder, StyledString, WriteColor};
+
+    #[test]
+    fn push_and_pop() {
+        let mut stack = StyleStack::new(Recorder::new());
+        stack.push(&spec("fg:red bg:white")).unwrap();
+        write!(stack, "a").unwrap();
+        stack.push(&spec("fg:blue bold")).unwrap();
+        assert_eq!(stack.depth(), 2);
+        assert_eq!(stack.current(), &spec("fg:blue bg:white bold"));
+        write!(stack, "b").unwrap();
+        stack.pop().unwrap();
+        write!(stack, "c").unwrap();
+        stack.pop().unwrap();
+        stack.pop().unwrap();
+        assert_eq!(stack.depth(), 0);
+        write!(stack, "d").unwrap();
+        assert_eq!(
+            stack.into_inner().render(),
+            "[fg:red bg:white]a[/][fg:blue bg:white bold]b[/]\
+             [fg:red bg:white]c[/]d"
+        );
+    }
+
+    #[test]
+    fn reset_restores_layer() {
+        let mut stack = StyleStack::new(Ansi::new(vec![]));
+        stack.push(&spec("fg:red")).unwrap();
+        StyledString::styled(&spec("italic"), "a")
+            .write_to(&mut stack)
+            .unwrap();
+        write!(stack, "b").unwrap();
+        stack.set_color(&spec("underline")).unwrap();
+        write!(stack, "c").unwrap();
+        stack.reset().unwrap();
+        stack.pop().unwrap();
+        assert_eq!(
+            stack.into_inner().into_inner(),
+            b"\x1B[0;31m\x1B[0;3;31ma\x1B[0;31mb\x1B[0;4;31mc\x1B[0;31m\x1B[0m"
+        );
+    }
+}
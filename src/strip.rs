@@ -0,0 +1,241 @@
+use std::io;
+
+use crate::{ColorSpec, HyperlinkSpec, WriteColor};
+
+/// Satisfies `WriteColor` by removing all escape sequences from the bytes
+/// written to it, and dropping all color information.
+///
+/// This is useful for writing the output of another program, which may
+/// contain its own ANSI escape sequences, to a destination that doesn't
+/// support them, such as a log file.
+///
+/// The following are removed:
+///
+/// * Control sequences (CSI), such as `ESC [ 1 ; 31 m`.
+/// * Operating system commands (OSC), such as the hyperlinks written by
+///   `Ansi::set_hyperlink`, terminated by either `BEL` or `ESC \`.
+/// * Device control strings, privacy messages and application program
+///   commands, which are terminated like operating system commands.
+/// * All other escape sequences, such as `ESC 7` or `ESC ( B`.
+///
+/// Escape sequences may be split across any number of calls to `write`.
+/// All other bytes, including invalid UTF-8, are passed through unchanged.
+#[derive(Clone, Debug)]
+pub struct StripAnsi<W> {
+    wtr: W,
+    state: State,
+}
+
+/// The position within an escape sequence while scanning a byte stream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum State {
+    /// Not in an escape sequence.
+    Ground,
+    /// Just after `ESC`.
+    Escape,
+    /// In an escape sequence with intermediate bytes, e.g., `ESC ( B`.
+    EscapeIntermediate,
+    /// In a control sequence, i.e., after `ESC [`.
+    Csi,
+    /// In a string terminated by `BEL` or `ESC \`, e.g., after `ESC ]`.
+    String,
+    /// Just after `ESC` in a string.
+    StringEscape,
+}
+
+impl<W: io::Write> StripAnsi<W> {
+    /// Create a new writer that removes all escape sequences before writing
+    /// to the given writer.
+    pub fn new(wtr: W) -> StripAnsi<W> {
+        StripAnsi { wtr, state: State::Ground }
+    }
+
+    /// Consume this `StripAnsi` value and return the inner writer.
+    ///
+    /// An escape sequence that is still incomplete is discarded.
+    pub fn into_inner(self) -> W {
+        self.wtr
+    }
+
+    /// Return a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.wtr
+    }
+
+    /// Return a mutable reference to the inner writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.wtr
+    }
+}
+
+impl State {
+    /// Returns the state after the given byte, and whether the byte is text
+    /// that should be kept.
+    pub(crate) fn next(self, b: u8) -> (State, bool) {
+        match self {
+            State::Ground if b == 0x1B => (State::Escape, false),
+            State::Ground => (State::Ground, true),
+            State::Escape => match b {
+                b'[' => (State::Csi, false),
+                b']' | b'P' | b'X' | b'^' | b'_' => (State::String, false),
+                0x1B => (State::Escape, false),
+                0x20..=0x2F => (State::EscapeIntermediate, false),
+                0x30..=0x7E => (State::Ground, false),
+                // Not an escape sequence, so only the `ESC` is dropped.
+                _ => State::Ground.next(b),
+            },
+            State::EscapeIntermediate => match b {
+                0x1B => (State::Escape, false),
+                0x20..=0x2F => (State::EscapeIntermediate, false),
+                0x30..=0x7E => (State::Ground, false),
+                _ => State::Ground.next(b),
+            },
+            State::Csi => match b {
+                0x1B => (State::Escape, false),
+                0x20..=0x3F => (State::Csi, false),
+                0x40..=0x7E => (State::Ground, false),
+                // A malformed control sequence ends at the first byte that
+                // can't be part of one.
+                _ => State::Ground.next(b),
+            },
+            State::String => match b {
+                0x07 => (State::Ground, false),
+                0x1B => (State::StringEscape, false),
+                _ => (State::String, false),
+            },
+            State::StringEscape => match b {
+                b'\\' => (State::Ground, false),
+                // An `ESC` that isn't a string terminator still ends the
+                // string, and starts a new escape sequence.
+                _ => State::Escape.next(b),
+            },
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for StripAnsi<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut start = None;
+        for (i, &b) in buf.iter().enumerate() {
+            let (state, keep) = self.state.next(b);
+            self.state = state;
+            match (keep, start) {
+                (true, None) => start = Some(i),
+                (false, Some(s)) => {
+                    self.wtr.write_all(&buf[s..i])?;
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(s) = start {
+            self.wtr.write_all(&buf[s..])?;
+        }
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+}
+
+impl<W: io::Write> WriteColor for StripAnsi<W> {
+    #[inline]
+    fn supports_color(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn supports_hyperlinks(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn set_color(&mut self, _: &ColorSpec) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn set_hyperlink(&mut self, _: &HyperlinkSpec) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn reset(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn is_synchronous(&self) -> bool {
+        false
+    }
+}
+
+/// Returns a copy of the given bytes with all escape sequences removed.
+///
+/// See [`StripAnsi`] for which escape sequences are removed.
+pub fn strip_ansi(bytes: &[u8]) -> Vec<u8> {
+    let mut wtr = StripAnsi::new(Vec::with_capacity(bytes.len()));
+    // Writing to a `Vec<u8>` never fails.
+    io::Write::write_all(&mut wtr, bytes).unwrap();
+    wtr.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{strip_ansi, StripAnsi};
+    use crate::{Ansi, Color, ColorSpec, HyperlinkSpec, WriteColor};
+
+    #[test]
+    fn control_sequences() {
+        assert_eq!(strip_ansi(b"\x1B[1;31mred\x1B[0m"), b"red");
+        assert_eq!(strip_ansi(b"a\x1B[2Kb\x1B[?25lc"), b"abc");
+        assert_eq!(strip_ansi(b"\x1B[4:3;58;2;255;0;0mx"), b"x");
+    }
+
+    #[test]
+    fn hyperlinks() {
+        let mut wtr = Ansi::new(vec![]);
+        let mut spec = ColorSpec::new();
+        spec.set_fg(Some(Color::Blue)).set_underline(true);
+        wtr.set_hyperlink(&HyperlinkSpec::open(b"https://example.com"))
+            .unwrap();
+        wtr.set_color(&spec).unwrap();
+        write!(wtr, "link").unwrap();
+        wtr.reset().unwrap();
+        wtr.set_hyperlink(&HyperlinkSpec::close()).unwrap();
+        assert_eq!(strip_ansi(&wtr.into_inner()), b"link");
+
+        let bel = b"\x1B]8;;https://example.com\x07link\x1B]8;;\x07";
+        assert_eq!(strip_ansi(bel), b"link");
+    }
+
+    #[test]
+    fn other_escapes() {
+        assert_eq!(strip_ansi(b"\x1B7a\x1B8\x1B(Bb\x1B=c"), b"abc");
+        assert_eq!(strip_ansi(b"\x1BPq#0\x1B\\d"), b"d");
+        // An `ESC` that doesn't start a sequence is dropped on its own.
+        assert_eq!(strip_ansi(b"a\x1B\nb"), b"a\nb");
+        // So is a control sequence interrupted by another one.
+        assert_eq!(strip_ansi(b"\x1B[1\x1B[31mc"), b"c");
+    }
+
+    #[test]
+    fn split_writes() {
+        let input = b"\x1B[1;31mfoo\x1B]8;;file:///\x1B\\bar\x1B[0m baz";
+        let mut wtr = StripAnsi::new(vec![]);
+        for b in input.iter() {
+            wtr.write_all(std::slice::from_ref(b)).unwrap();
+        }
+        assert_eq!(wtr.into_inner(), b"foobar baz");
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let input = b"\xFF\xFE\x1B[31m\xC3(\x1B[0m\x9B";
+        assert_eq!(strip_ansi(input), b"\xFF\xFE\xC3(\x9B");
+    }
+}
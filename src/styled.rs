@@ -0,0 +1,357 @@
+use std::fmt;
+use std::io;
+
+use crate::{ColorSpec, WriteColor};
+
+/// An owned string made of spans of text, each with its own style.
+///
+/// A `StyledString` makes it possible to build a message that mixes styles
+/// without knowing where it will be written. It can be written to any
+/// `WriteColor` with [`StyledString::write_to`], and its `Display`
+/// implementation writes the text without any style.
+///
+/// Every span is written with a color specification that resets, so styles
+/// never carry over from one span to the next, and the writer is reset after
+/// the last styled span.
+///
+/// # Example
+///
+/// ```
+/// use termcolor2::{Color, ColorSpec, Recorder, StyledString};
+///
+/// let mut msg = StyledString::new();
+/// msg.push(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true), "error")
+///     .push_plain(": file not found");
+/// assert_eq!(msg.to_string(), "error: file not found");
+/// assert_eq!(msg.width(), 21);
+///
+/// let mut rec = Recorder::new();
+/// msg.write_to(&mut rec)?;
+/// assert_eq!(rec.render(), "[fg:red bold]error[/]: file not found");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct StyledString {
+    spans: Vec<(ColorSpec, String)>,
+}
+
+impl StyledString {
+    /// Create a new empty styled string.
+    pub fn new() -> StyledString {
+        StyledString::default()
+    }
+
+    /// Create a new styled string with a single span.
+    pub fn styled(spec: &ColorSpec, text: &str) -> StyledString {
+        let mut s = StyledString::new();
+        s.push(spec, text);
+        s
+    }
+
+    /// Create a new styled string made of the given styled strings, in
+    /// order.
+    pub fn concat<I>(strings: I) -> StyledString
+    where
+        I: IntoIterator<Item = StyledString>,
+    {
+        let mut s = StyledString::new();
+        for other in strings {
+            s.append(&other);
+        }
+        s
+    }
+
+    /// Add text with the given style at the end of this string.
+    ///
+    /// If the last span has the same style, then the text is added to it.
+    /// Whether the given spec resets is ignored, since every span resets.
+    pub fn push(&mut self, spec: &ColorSpec, text: &str) -> &mut StyledString {
+        if text.is_empty() {
+            return self;
+        }
+        let mut spec = spec.clone();
+        spec.set_reset(true);
+        match self.spans.last_mut() {
+            Some((last, s)) if *last == spec => s.push_str(text),
+            _ => self.spans.push((spec, text.to_string())),
+        }
+        self
+    }
+
+    /// Add text without any style at the end of this string.
+    pub fn push_plain(&mut self, text: &str) -> &mut StyledString {
+        self.push(&ColorSpec::new(), text)
+    }
+
+    /// Add all of the spans of the given styled string at the end of this
+    /// one.
+    pub fn append(&mut self, other: &StyledString) -> &mut StyledString {
+        for (spec, text) in &other.spans {
+            self.push(spec, text);
+        }
+        self
+    }
+
+    /// Return the spans of this string, in order.
+    pub fn spans(&self) -> &[(ColorSpec, String)] {
+        &self.spans
+    }
+
+    /// Returns true if this string has no text.
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Returns the number of terminal columns needed to display this string,
+    /// ignoring styles.
+    ///
+    /// Most characters take up one column. East Asian wide characters and
+    /// emoji take up two, while combining marks, zero width characters and
+    /// control characters take up none.
+    pub fn width(&self) -> usize {
+        self.spans
+            .iter()
+            .flat_map(|(_, text)| text.chars())
+            .map(char_width)
+            .sum()
+    }
+
+    /// Write this string to the given writer, setting the style of each
+    /// span.
+    ///
+    /// The writer is reset after the last span if it has a style.
+    pub fn write_to<W: WriteColor + ?Sized>(
+        &self,
+        wtr: &mut W,
+    ) -> io::Result<()> {
+        let mut styled = false;
+        for (spec, text) in &self.spans {
+            if spec.is_none() {
+                if styled {
+                    wtr.reset()?;
+                    styled = false;
+                }
+            } else {
+                wtr.set_color(spec)?;
+                styled = true;
+            }
+            wtr.write_all(text.as_bytes())?;
+        }
+        if styled {
+            wtr.reset()?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StyledString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (_, text) in &self.spans {
+            f.write_str(text)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> From<&'a str> for StyledString {
+    fn from(text: &'a str) -> StyledString {
+        let mut s = StyledString::new();
+        s.push_plain(text);
+        s
+    }
+}
+
+impl From<String> for StyledString {
+    fn from(text: String) -> StyledString {
+        StyledString::from(text.as_str())
+    }
+}
+
+impl FromIterator<StyledString> for StyledString {
+    fn from_iter<I: IntoIterator<Item = StyledString>>(
+        iter: I,
+    ) -> StyledString {
+        StyledString::concat(iter)
+    }
+}
+
+/// Returns the number of terminal columns taken up by the given character.
+///
+/// This is an approximation of the Unicode East Asian Width property that
+/// covers the common wide and zero width ranges.
+pub(crate) fn char_width(c: char) -> usize {
+    match c as u32 {
+        c if c < 0x20 || (0x7F..0xA0).contains(&c) => 0,
+        // Combining marks and zero width characters.
+        0x0300..=0x036F
+        | 0x0483..=0x0489
+        | 0x0591..=0x05BD
+        | 0x0610..=0x061A
+        | 0x064B..=0x065F
+        | 0x1AB0..=0x1AFF
+        | 0x1DC0..=0x1DFF
+        | 0x200B..=0x200F
+        | 0x2028..=0x202E
+        | 0x2060..=0x2064
+        | 0x20D0..=0x20FF
+        | 0xFE00..=0xFE0F
+        | 0xFE20..=0xFE2F
+        | 0xFEFF
+        | 0xE0100..=0xE01EF => 0,
+        // East Asian wide and fullwidth characters, and emoji.
+        0x1100..=0x115F
+        | 0x231A..=0x231B
+        | 0x2329..=0x232A
+        | 0x23E9..=0x23EC
+        | 0x23F0
+        | 0x23F3
+        | 0x25FD..=0x25FE
+        | 0x2614..=0x2615
+        | 0x2648..=0x2653
+        | 0x267F
+        | 0x2693
+        | 0x26A1
+        | 0x26AA..=0x26AB
+        | 0x26BD..=0x26BE
+        | 0x26C4..=0x26C5
+        | 0x26CE
+        | 0x26D4
+        | 0x26EA
+        | 0x26F2..=0x26F3
+        | 0x26F5
+        | 0x26FA
+        | 0x26FD
+        | 0x2705
+        | 0x270A..=0x270B
+        | 0x2728
+        | 0x274C
+        | 0x274E
+        | 0x2753..=0x2755
+        | 0x2757
+        | 0x2795..=0x2797
+        | 0x27B0
+        | 0x27BF
+        | 0x2B1B..=0x2B1C
+        | 0x2B50
+        | 0x2B55
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xA960..=0xA97F
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE10..=0xFE19
+        | 0xFE30..=0xFE6F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x16FE0..=0x16FE4
+        | 0x17000..=0x18CFF
+        | 0x1B000..=0x1B2FF
+        | 0x1F004
+        | 0x1F0CF
+        | 0x1F18E
+        | 0x1F191..=0x1F19A
+        | 0x1F200..=0x1F251
+        | 0x1F300..=0x1F320
+        | 0x1F32D..=0x1F335
+        | 0x1F337..=0x1F37C
+        | 0x1F37E..=0x1F393
+        | 0x1F3A0..=0x1F3CA
+        | 0x1F3CF..=0x1F3D3
+        | 0x1F3E0..=0x1F3F0
+        | 0x1F3F4
+        | 0x1F3F8..=0x1F43E
+        | 0x1F440
+        | 0x1F442..=0x1F4FC
+        | 0x1F4FF..=0x1F53D
+        | 0x1F54B..=0x1F54E
+        | 0x1F550..=0x1F567
+        | 0x1F57A
+        | 0x1F595..=0x1F596
+        | 0x1F5A4
+        | 0x1F5FB..=0x1F64F
+        | 0x1F680..=0x1F6C5
+        | 0x1F6CC
+        | 0x1F6D0..=0x1F6D2
+        | 0x1F6D5..=0x1F6D7
+        | 0x1F6DC..=0x1F6DF
+        | 0x1F6EB..=0x1F6EC
+        | 0x1F6F4..=0x1F6FC
+        | 0x1F7E0..=0x1F7EB
+        | 0x1F7F0
+        | 0x1F90C..=0x1F93A
+        | 0x1F93C..=0x1F945
+        | 0x1F947..=0x1F9FF
+        | 0x1FA70..=0x1FAFF
+        | 0x20000..=0x2FFFD
+        | 0x30000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StyledString;
+    use crate::tests::spec;
+    use crate::{Ansi, Color, ColorSpec, Recorder};
+
+    #[test]
+    fn push_and_append() {
+        let mut s = StyledString::from("a");
+        s.push_plain("b").push(&spec("fg:red"), "c");
+        // A spec that doesn't reset is stored as one that does.
+        s.push(&spec("fg:red noreset"), "d").push(&spec("bold"), "");
+        assert_eq!(
+            s.spans(),
+            &[
+                (spec(""), "ab".to_string()),
+                (spec("fg:red"), "cd".to_string())
+            ]
+        );
+
+        let t = StyledString::styled(&spec("bold"), "e");
+        let all = StyledString::concat(vec![s.clone(), t.clone(), s.clone()]);
+        assert_eq!(all.spans().len(), 5);
+        assert_eq!(all.to_string(), "abcdeabcd");
+        let collected: StyledString = vec![s, t].into_iter().collect();
+        assert_eq!(collected.to_string(), "abcde");
+        assert!(StyledString::new().is_empty());
+    }
+
+    #[test]
+    fn write_to() {
+        let mut s = StyledString::new();
+        s.push(&spec("fg:red bold"), "a")
+            .push(&spec("italic"), "b")
+            .push_plain("c")
+            .push(&spec("fg:blue"), "d");
+
+        let mut rec = Recorder::new();
+        s.write_to(&mut rec).unwrap();
+        assert_eq!(
+            rec.render(),
+            "[fg:red bold]a[/][italic]b[/]c[fg:blue]d[/]"
+        );
+
+        let mut wtr = Ansi::new(vec![]);
+        s.write_to(&mut wtr).unwrap();
+        assert_eq!(
+            wtr.into_inner(),
+            b"\x1B[0;1;31ma\x1B[0;3mb\x1B[0mc\x1B[0;34md\x1B[0m"
+        );
+    }
+
+    #[test]
+    fn width() {
+        let mut s = StyledString::styled(
+            ColorSpec::new().set_fg(Some(Color::Green)),
+            "ok",
+        );
+        s.push_plain(" caf\u{e9} e\u{301}");
+        assert_eq!(s.width(), 9);
+        s.push_plain("\u{4f60}\u{597d}\u{1F600}");
+        assert_eq!(s.width(), 15);
+    }
+}
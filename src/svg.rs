@@ -0,0 +1,526 @@
+use std::fmt::Write as _;
+use std::io;
+
+use crate::html::is_safe_link;
+use crate::styled::char_width;
+use crate::{Color, ColorSpec, HyperlinkSpec, Palette, WriteColor};
+
+/// The width of a character cell, relative to the font size.
+const CELL_WIDTH: f64 = 0.6;
+/// The height of a line, relative to the font size.
+const LINE_HEIGHT: f64 = 1.2;
+/// The number of columns between tab stops.
+const TAB_WIDTH: usize = 8;
+
+/// The red, green and blue components of a color.
+type Rgb = (u8, u8, u8);
+
+/// Satisfies `WriteColor` by recording styled text, which can then be
+/// rendered as an SVG image that looks like a terminal screenshot.
+///
+/// The image is self-contained: text is laid out on a grid of monospace
+/// character cells, on top of the palette's background color. Foreground and
+/// background colors are taken from each `ColorSpec` and resolved with a
+/// configurable [`Palette`]. Bold, dimmed, italic, underline, strikethrough,
+/// overline, reverse and hidden text are rendered, and hyperlinks become
+/// links in the image. As with [`Html`](crate::Html), only URIs with an
+/// `http`, `https`, `file` or `mailto` scheme are linked, since links to
+/// other URIs, such as `javascript:` ones, can run script when the image is
+/// opened.
+///
+/// Text is laid out one character per cell, with tab stops every 8 columns.
+/// Wide characters, such as CJK ideographs and most emoji, take up two cells,
+/// while zero width characters, such as combining marks, take up none.
+/// Carriage returns and other control characters are ignored, and invalid
+/// UTF-8 is replaced with `U+FFFD`.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Write;
+/// use termcolor2::{Color, ColorSpec, Svg, WriteColor};
+///
+/// let mut svg = Svg::new();
+/// svg.set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))?;
+/// write!(svg, "Finished")?;
+/// svg.reset()?;
+/// writeln!(svg, " release target(s)")?;
+/// let image = svg.render();
+/// assert!(image.starts_with("<svg"));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Svg {
+    palette: Palette,
+    font_size: u32,
+    font_family: String,
+    /// The recorded text, as runs of bytes with the same style and
+    /// hyperlink.
+    runs: Vec<Run>,
+    style: ColorSpec,
+    link: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Debug)]
+struct Run {
+    style: ColorSpec,
+    link: Option<Vec<u8>>,
+    text: Vec<u8>,
+}
+
+impl Default for Svg {
+    fn default() -> Svg {
+        Svg::new()
+    }
+}
+
+impl Svg {
+    /// Create a new, empty recording using the default palette and a 14px
+    /// monospace font.
+    pub fn new() -> Svg {
+        Svg {
+            palette: Palette::default(),
+            font_size: 14,
+            font_family: "monospace".to_string(),
+            runs: vec![],
+            style: ColorSpec::new(),
+            link: None,
+        }
+    }
+
+    /// Return the palette used for colors.
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Set the palette used for colors, including the default foreground
+    /// and background colors of the image.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Get the font size in pixels.
+    pub fn font_size(&self) -> u32 {
+        self.font_size
+    }
+
+    /// Set the font size in pixels. The size of the image is proportional to
+    /// it.
+    pub fn set_font_size(&mut self, size: u32) {
+        self.font_size = size;
+    }
+
+    /// Get the font family.
+    pub fn font_family(&self) -> &str {
+        &self.font_family
+    }
+
+    /// Set the font family, as a CSS font family list, e.g.,
+    /// `"'Fira Code', monospace"`. It should name a monospace font.
+    pub fn set_font_family(&mut self, family: &str) {
+        self.font_family = family.to_string();
+    }
+
+    /// Render the recorded text as an SVG image.
+    pub fn render(&self) -> String {
+        let lines = self.layout();
+        let size = self.font_size as f64;
+        let (cell, height, pad) =
+            (size * CELL_WIDTH, size * LINE_HEIGHT, size);
+        let columns = lines
+            .iter()
+            .map(|line| {
+                line.iter().map(|c| c.column + c.width).max().unwrap_or(0)
+            })
+            .max()
+            .unwrap_or(0);
+        let width = columns as f64 * cell + 2.0 * pad;
+        let total_height = lines.len() as f64 * height + 2.0 * pad;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" \
+             height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+            w = num(width),
+            h = num(total_height),
+        );
+        let _ = writeln!(
+            svg,
+            "<rect width=\"100%\" height=\"100%\" rx=\"{}\" fill=\"{}\"/>",
+            num(size / 2.0),
+            hex(self.palette.background()),
+        );
+        let _ = writeln!(
+            svg,
+            "<g font-family=\"{}\" font-size=\"{}\" xml:space=\"preserve\">",
+            escape(&self.font_family),
+            self.font_size,
+        );
+        for (row, chunks) in lines.iter().enumerate() {
+            let top = pad + row as f64 * height;
+            // Backgrounds first, so that they never cover any text.
+            for chunk in chunks {
+                if let Some(bg) = chunk.bg {
+                    let _ = writeln!(
+                        svg,
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                         fill=\"{}\"/>",
+                        num(pad + chunk.column as f64 * cell),
+                        num(top),
+                        num(chunk.width as f64 * cell),
+                        num(height),
+                        hex(bg),
+                    );
+                }
+            }
+            for chunk in chunks {
+                if chunk.style.hidden() || chunk.text.trim().is_empty() {
+                    continue;
+                }
+                let mut attrs = format!(
+                    "x=\"{}\" y=\"{}\" textLength=\"{}\" fill=\"{}\"",
+                    num(pad + chunk.column as f64 * cell),
+                    // Place the baseline so that the text is centered within
+                    // the line.
+                    num(top + height * 0.8),
+                    num(chunk.width as f64 * cell),
+                    hex(chunk.fg),
+                );
+                let spec = &chunk.style;
+                if spec.bold() {
+                    attrs.push_str(" font-weight=\"bold\"");
+                }
+                if spec.italic() {
+                    attrs.push_str(" font-style=\"italic\"");
+                }
+                if spec.dimmed() {
+                    attrs.push_str(" opacity=\"0.5\"");
+                }
+                let decorations: Vec<&str> = [
+                    (spec.underline(), "underline"),
+                    (spec.strikethrough(), "line-through"),
+                    (spec.overline(), "overline"),
+                ]
+                .iter()
+                .filter(|&&(yes, _)| yes)
+                .map(|&(_, name)| name)
+                .collect();
+                if !decorations.is_empty() {
+                    let _ = write!(
+                        attrs,
+                        " text-decoration=\"{}\"",
+                        decorations.join(" ")
+                    );
+                }
+                let text =
+                    format!("<text {}>{}</text>", attrs, escape(&chunk.text));
+                match chunk.link {
+                    None => svg.push_str(&text),
+                    Some(uri) => {
+                        let _ = write!(
+                            svg,
+                            "<a href=\"{}\">{}</a>",
+                            escape(&String::from_utf8_lossy(uri)),
+                            text
+                        );
+                    }
+                }
+                svg.push('\n');
+            }
+        }
+        svg.push_str("</g>\n</svg>\n");
+        svg
+    }
+
+    /// Split the recorded runs into lines of positioned chunks.
+    fn layout(&self) -> Vec<Vec<Chunk<'_>>> {
+        let mut lines = vec![vec![]];
+        let mut column = 0;
+        for run in &self.runs {
+            let (fg, bg) = self.colors(&run.style);
+            let mut chunk = Chunk {
+                style: &run.style,
+                link: run.link.as_deref(),
+                fg,
+                bg,
+                column,
+                width: 0,
+                text: String::new(),
+            };
+            for c in String::from_utf8_lossy(&run.text).chars() {
+                match c {
+                    '\n' => {
+                        let next = Chunk { column: 0, ..chunk.empty() };
+                        let done = std::mem::replace(&mut chunk, next);
+                        push_chunk(&mut lines, done);
+                        lines.push(vec![]);
+                        column = 0;
+                    }
+                    '\t' => {
+                        let spaces = TAB_WIDTH - column % TAB_WIDTH;
+                        chunk.text.push_str(&" ".repeat(spaces));
+                        chunk.width += spaces;
+                        column += spaces;
+                    }
+                    c if c.is_control() => {}
+                    c => {
+                        // Zero width characters, such as combining marks,
+                        // are kept with the character before them.
+                        let width = char_width(c);
+                        chunk.text.push(c);
+                        chunk.width += width;
+                        column += width;
+                    }
+                }
+            }
+            push_chunk(&mut lines, chunk);
+        }
+        // A trailing new line doesn't start another line.
+        if lines.len() > 1 && lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        lines
+    }
+
+    /// Returns the foreground and background colors of the given style,
+    /// after applying reverse video.
+    fn colors(&self, spec: &ColorSpec) -> (Rgb, Option<Rgb>) {
+        // The default color is the same as no color.
+        let resolve = |c: &Color| match *c {
+            Color::Default => None,
+            ref c => Some(self.palette.resolve(c, spec.intense())),
+        };
+        let (fg, bg) =
+            (spec.fg().and_then(resolve), spec.bg().and_then(resolve));
+        if spec.reverse() {
+            let bg = bg.unwrap_or(self.palette.background());
+            (bg, Some(fg.unwrap_or(self.palette.foreground())))
+        } else {
+            (fg.unwrap_or(self.palette.foreground()), bg)
+        }
+    }
+}
+
+/// A piece of a run that lies on a single line.
+struct Chunk<'a> {
+    style: &'a ColorSpec,
+    link: Option<&'a [u8]>,
+    fg: Rgb,
+    bg: Option<Rgb>,
+    column: usize,
+    width: usize,
+    text: String,
+}
+
+impl<'a> Chunk<'a> {
+    fn empty(&self) -> Chunk<'a> {
+        Chunk {
+            style: self.style,
+            link: self.link,
+            fg: self.fg,
+            bg: self.bg,
+            column: self.column,
+            width: 0,
+            text: String::new(),
+        }
+    }
+}
+
+fn push_chunk<'a>(lines: &mut [Vec<Chunk<'a>>], chunk: Chunk<'a>) {
+    if chunk.width > 0 {
+        if let Some(line) = lines.last_mut() {
+            line.push(chunk);
+        }
+    }
+}
+
+/// Format a number for an SVG attribute, with at most two decimals.
+fn num(n: f64) -> String {
+    let s = format!("{:.2}", n);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn hex((r, g, b): Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Escape the characters that are special in XML text and attributes.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl io::Write for Svg {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.runs.last_mut() {
+            Some(run) if run.style == self.style && run.link == self.link => {
+                run.text.extend_from_slice(buf);
+            }
+            _ => self.runs.push(Run {
+                style: self.style.clone(),
+                link: self.link.clone(),
+                text: buf.to_vec(),
+            }),
+        }
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl WriteColor for Svg {
+    #[inline]
+    fn supports_color(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn supports_hyperlinks(&self) -> bool {
+        true
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.style = self.style.apply(spec);
+        Ok(())
+    }
+
+    fn set_hyperlink(&mut self, link: &HyperlinkSpec) -> io::Result<()> {
+        self.link =
+            link.uri().filter(|uri| is_safe_link(uri)).map(|uri| uri.to_vec());
+        Ok(())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.style.clear();
+        Ok(())
+    }
+
+    #[inline]
+    fn is_synchronous(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::Svg;
+    use crate::tests::spec;
+    use crate::{HyperlinkSpec, Palette, WriteColor};
+
+    #[test]
+    fn empty() {
+        assert_eq!(
+            Svg::new().render(),
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"28\" \
+             height=\"44.8\" viewBox=\"0 0 28 44.8\">\n\
+             <rect width=\"100%\" height=\"100%\" rx=\"7\" \
+             fill=\"#000000\"/>\n\
+             <g font-family=\"monospace\" font-size=\"14\" \
+             xml:space=\"preserve\">\n\
+             </g>\n</svg>\n"
+        );
+    }
+
+    #[test]
+    fn styled_text() {
+        let mut svg = Svg::new();
+        svg.set_font_size(10);
+        svg.set_color(&spec("fg:red bg:blue bold underline")).unwrap();
+        write!(svg, "a<b").unwrap();
+        svg.reset().unwrap();
+        writeln!(svg, " ok").unwrap();
+        let image = svg.render();
+        assert!(image.contains(
+            "<rect x=\"10\" y=\"10\" width=\"18\" height=\"12\" \
+             fill=\"#0000ee\"/>\n"
+        ));
+        assert!(image.contains(
+            "<text x=\"10\" y=\"19.6\" textLength=\"18\" fill=\"#cd0000\" \
+             font-weight=\"bold\" text-decoration=\"underline\">a&lt;b</text>"
+        ));
+        assert!(image.contains(
+            "<text x=\"28\" y=\"19.6\" textLength=\"18\" \
+             fill=\"#e5e5e5\"> ok</text>"
+        ));
+        // 6 columns and 1 line, plus padding.
+        assert!(image.contains("viewBox=\"0 0 56 32\""));
+    }
+
+    #[test]
+    fn layout() {
+        let mut palette = Palette::new();
+        palette.set_foreground((1, 2, 3)).set_background((4, 5, 6));
+        let mut svg = Svg::new();
+        svg.set_font_size(10);
+        svg.set_palette(palette);
+        svg.set_color(&spec("reverse")).unwrap();
+        // The multi-byte character is split across writes.
+        svg.write_all(b"ab\tc\r\n\xC3").unwrap();
+        svg.write_all(b"\xA9").unwrap();
+        svg.set_hyperlink(&HyperlinkSpec::open(b"https://a.b/?x&y")).unwrap();
+        write!(svg, "d").unwrap();
+        let image = svg.render();
+        // The tab extends the first chunk to the next tab stop.
+        assert!(image.contains(
+            "<rect x=\"10\" y=\"10\" width=\"54\" height=\"12\" \
+             fill=\"#010203\"/>"
+        ));
+        assert!(image.contains(
+            "<text x=\"10\" y=\"19.6\" textLength=\"54\" \
+             fill=\"#040506\">ab      c</text>"
+        ));
+        assert!(image.contains(
+            "<text x=\"10\" y=\"31.6\" textLength=\"6\" \
+             fill=\"#040506\">\u{e9}</text>"
+        ));
+        assert!(image.contains(
+            "<a href=\"https://a.b/?x&amp;y\"><text x=\"16\" y=\"31.6\""
+        ));
+        assert!(image.contains("viewBox=\"0 0 74 44\""));
+    }
+
+    #[test]
+    fn wide_characters() {
+        let mut svg = Svg::new();
+        svg.set_font_size(10);
+        write!(svg, "日本e\u{301}").unwrap();
+        svg.set_color(&spec("bold")).unwrap();
+        write!(svg, "x").unwrap();
+        let image = svg.render();
+        assert!(image.contains(
+            "<text x=\"10\" y=\"19.6\" textLength=\"30\" \
+             fill=\"#e5e5e5\">日本e\u{301}</text>"
+        ));
+        // The next chunk starts after the 5 columns taken up so far.
+        assert!(image.contains("<text x=\"40\" y=\"19.6\" textLength=\"6\""));
+    }
+
+    #[test]
+    fn unsafe_links_are_dropped() {
+        let mut svg = Svg::new();
+        svg.set_hyperlink(&HyperlinkSpec::open(b"javascript:alert(1)"))
+            .unwrap();
+        write!(svg, "a").unwrap();
+        let image = svg.render();
+        assert!(!image.contains("<a "));
+        assert!(image.contains(">a</text>"));
+    }
+}
@@ -3,6 +3,7 @@
 /// This module includes parsers for the following color formats:
 /// - **RGB**: A color defined by three numeric values (e.g., "rgb(255, 0, 0)") or in "rgb(x, y, z)" format where `x`, `y`, and `z` are integer values (either decimal or hexadecimal).
 /// - **Hex**: A color defined in hexadecimal format (e.g., "#FF0000").
+/// - **Color functions**: The CSS functional notations `hsl()`, `hwb()`, `lab()`, `lch()`, `oklab()` and `oklch()`, as well as `hsv()` (e.g., "hsl(210 80% 60%)").
 /// - **Ansi256**: A 256-color ANSI code (e.g., "137" or "0x89" for hexadecimal).
 /// - **Other formats**: This can include named colors or additional color formats defined by specific comma-separated values.
 use crate::{Color, ParseColorError, ParseColorErrorKind};
@@ -94,7 +95,7 @@ pub fn parse_rgb(s: &str) -> Result<Color, ParseColorError> {
     }
 }
 
-/// Parses a string in hex format (e.g., "#FF0000") into a `Color::Hex`.
+/// Parses a string in hex format (e.g., "#FF0000", or "#FF000080" with alpha) into a `Color::Hex`.
 ///
 /// # Parameters:
 /// - `s`: A string slice containing the hexadecimal color.
@@ -110,15 +111,470 @@ pub fn parse_hex(s: &str) -> Result<Color, ParseColorError> {
         });
     }
 
-    if s.len() != 4 && s.len() != 7 {
+    if ![4, 5, 7, 9].contains(&s.len()) {
         return Err(ParseColorError {
             kind: ParseColorErrorKind::InvalidHex,
             given: s.to_string(),
         });
     }
 
-    let upper = s.to_ascii_uppercase();
-    Ok(Color::Hex(Box::leak(upper.into_boxed_str())))
+    Ok(Color::Hex(s.to_ascii_uppercase()))
+}
+
+/// Decodes a hex color string (e.g., "#FF0000" or "#F00") into its red, green
+/// and blue components. The alpha component of "#RGBA" and "#RRGGBBAA" strings is ignored.
+///
+/// # Parameters:
+/// - `s`: A string slice containing the hexadecimal color, with or without the leading '#'.
+///
+/// # Returns:
+/// The decoded `(r, g, b)` triple. Strings that aren't 3, 4, 6 or 8 hex digits long decode to black.
+pub fn hex_to_rgb(s: &str) -> (u8, u8, u8) {
+    let hex = s.trim_start_matches('#');
+    let digit = |i: usize, len: usize| {
+        hex.get(i..i + len)
+            .and_then(|d| u8::from_str_radix(d, 16).ok())
+            .unwrap_or(0)
+    };
+    match hex.len() {
+        3 | 4 => (digit(0, 1) * 17, digit(1, 1) * 17, digit(2, 1) * 17),
+        6 | 8 => (digit(0, 2), digit(2, 2), digit(4, 2)),
+        _ => (0, 0, 0),
+    }
+}
+
+/// Decodes the alpha component of a hex color string (e.g., "#FF000080" or "#F008").
+///
+/// # Parameters:
+/// - `s`: A string slice containing the hexadecimal color, with or without the leading '#'.
+///
+/// # Returns:
+/// The alpha component, where 255 is opaque, or `None` if the string has no alpha component.
+pub fn hex_alpha(s: &str) -> Option<u8> {
+    let hex = s.trim_start_matches('#');
+    let digits =
+        |i: usize| hex.get(i..).and_then(|d| u8::from_str_radix(d, 16).ok());
+    match hex.len() {
+        4 => digits(3).map(|a| a * 17),
+        8 => digits(6),
+        _ => None,
+    }
+}
+
+/// A numeric component of a color function, e.g., the saturation of `hsl()`.
+struct Component {
+    /// The name used in error messages.
+    name: &'static str,
+    /// The value that `100%` stands for.
+    percent: f64,
+    /// The smallest and largest allowed values.
+    min: f64,
+    max: f64,
+    /// The allowed values, as shown in error messages.
+    range: &'static str,
+}
+
+const fn percentage(name: &'static str) -> Component {
+    Component {
+        name,
+        percent: 100.0,
+        min: 0.0,
+        max: 100.0,
+        range: "between 0% and 100%",
+    }
+}
+
+const fn axis(name: &'static str, percent: f64) -> Component {
+    Component {
+        name,
+        percent,
+        min: f64::NEG_INFINITY,
+        max: f64::INFINITY,
+        range: "any number",
+    }
+}
+
+const fn chroma(percent: f64) -> Component {
+    Component {
+        name: "chroma",
+        percent,
+        min: 0.0,
+        max: f64::INFINITY,
+        range: "at least 0",
+    }
+}
+
+const LAB_LIGHTNESS: Component = Component {
+    name: "lightness",
+    percent: 100.0,
+    min: 0.0,
+    max: 100.0,
+    range: "between 0 and 100",
+};
+
+const OKLAB_LIGHTNESS: Component = Component {
+    name: "lightness",
+    percent: 1.0,
+    min: 0.0,
+    max: 1.0,
+    range: "between 0 and 1",
+};
+
+const ALPHA: Component = Component {
+    name: "alpha",
+    percent: 1.0,
+    min: 0.0,
+    max: 1.0,
+    range: "between 0 and 1",
+};
+
+/// Parses a number, a percentage or the keyword "none", which stands for 0.
+///
+/// # Parameters:
+/// - `s`: A string slice containing the value, in lowercase.
+/// - `percent`: The value that `100%` stands for.
+///
+/// # Returns:
+/// The parsed value, or `None` if it isn't a finite number.
+fn parse_value(s: &str, percent: f64) -> Option<f64> {
+    if s == "none" {
+        return Some(0.0);
+    }
+    let (number, scale) = match s.strip_suffix('%') {
+        Some(number) => (number, percent / 100.0),
+        None => (s, 1.0),
+    };
+    number.parse::<f64>().ok().filter(|v| v.is_finite()).map(|v| v * scale)
+}
+
+/// Parses a hue, which is a number of degrees or an angle with one of the
+/// units `deg`, `grad`, `rad` or `turn`.
+///
+/// # Parameters:
+/// - `s`: A string slice containing the hue, in lowercase.
+///
+/// # Returns:
+/// The hue in degrees, in the range `[0, 360)`, or `None` if it is invalid.
+fn parse_hue(s: &str) -> Option<f64> {
+    let units = [
+        ("deg", 1.0),
+        ("grad", 0.9),
+        ("rad", 180.0 / std::f64::consts::PI),
+        ("turn", 360.0),
+    ];
+    let (number, scale) = units
+        .iter()
+        .find_map(|&(unit, scale)| s.strip_suffix(unit).map(|n| (n, scale)))
+        .unwrap_or((s, 1.0));
+    if number == "none" {
+        return Some(0.0);
+    }
+    let degrees = number.parse::<f64>().ok().filter(|v| v.is_finite())?;
+    Some((degrees * scale).rem_euclid(360.0))
+}
+
+/// Parses a color in one of the CSS Color Level 4 functional notations
+/// `hsl()`, `hwb()`, `lab()`, `lch()`, `oklab()` and `oklch()`, or in
+/// `hsv()`, which is written like `hsl()`.
+///
+/// The three components are separated by whitespace and may be followed by
+/// an alpha value after a `/`. In the legacy syntax, the components are
+/// separated by commas instead, and the alpha value is a fourth component.
+/// Colors outside of the sRGB gamut are mapped into it by reducing their
+/// chroma, which keeps their lightness and hue.
+///
+/// # Parameters:
+/// - `s`: A string slice containing the color, e.g., "hsl(210 80% 60%)".
+///
+/// # Returns:
+/// A `Result<Color, ParseColorError>`. On success, it returns `Color::Rgb(r, g, b)`,
+/// or a `Color::Hex` in the form `#RRGGBBAA` if the alpha value is below 1.
+/// On failure, it returns an error of type `ParseColorError` that is either about the notation,
+/// or names the component that is out of range.
+pub fn parse_function(s: &str) -> Result<Color, ParseColorError> {
+    let err = |kind| ParseColorError { kind, given: s.to_string() };
+    let invalid = || err(ParseColorErrorKind::InvalidFunction);
+
+    let lower = s.trim().to_ascii_lowercase();
+    let (name, args) = lower
+        .strip_suffix(')')
+        .and_then(|t| t.split_once('('))
+        .ok_or_else(invalid)?;
+    let components = match name.trim() {
+        "hsl" => [
+            None,
+            Some(percentage("saturation")),
+            Some(percentage("lightness")),
+        ],
+        "hsv" => {
+            [None, Some(percentage("saturation")), Some(percentage("value"))]
+        }
+        "hwb" => [
+            None,
+            Some(percentage("whiteness")),
+            Some(percentage("blackness")),
+        ],
+        "lab" => [
+            Some(LAB_LIGHTNESS),
+            Some(axis("a", 125.0)),
+            Some(axis("b", 125.0)),
+        ],
+        "lch" => [Some(LAB_LIGHTNESS), Some(chroma(150.0)), None],
+        "oklab" => {
+            [Some(OKLAB_LIGHTNESS), Some(axis("a", 0.4)), Some(axis("b", 0.4))]
+        }
+        "oklch" => [Some(OKLAB_LIGHTNESS), Some(chroma(0.4)), None],
+        _ => return Err(invalid()),
+    };
+
+    let (tokens, alpha) = if args.contains(',') {
+        let mut tokens: Vec<&str> = args.split(',').map(str::trim).collect();
+        if args.contains('/') || tokens.iter().any(|t| t.is_empty()) {
+            return Err(invalid());
+        }
+        let alpha = if tokens.len() == 4 { tokens.pop() } else { None };
+        (tokens, alpha)
+    } else {
+        let (args, alpha) = match args.split_once('/') {
+            Some((args, alpha)) => (args, Some(alpha.trim())),
+            None => (args, None),
+        };
+        (args.split_whitespace().collect::<Vec<&str>>(), alpha)
+    };
+    if tokens.len() != 3 {
+        return Err(invalid());
+    }
+
+    let check = |value: f64, comp: &Component| {
+        if value < comp.min || value > comp.max {
+            Err(err(ParseColorErrorKind::ComponentOutOfRange {
+                component: comp.name,
+                range: comp.range,
+            }))
+        } else {
+            Ok(value)
+        }
+    };
+    let alpha = match alpha {
+        Some(alpha) => check(
+            parse_value(alpha, ALPHA.percent).ok_or_else(invalid)?,
+            &ALPHA,
+        )?,
+        None => 1.0,
+    };
+    let mut v = [0.0; 3];
+    for (i, (token, comp)) in tokens.iter().zip(&components).enumerate() {
+        v[i] = match *comp {
+            None => parse_hue(token).ok_or_else(invalid)?,
+            Some(ref comp) => check(
+                parse_value(token, comp.percent).ok_or_else(invalid)?,
+                comp,
+            )?,
+        };
+    }
+
+    let (r, g, b) = match name.trim() {
+        "hsl" => hsl_to_srgb(v[0], v[1] / 100.0, v[2] / 100.0),
+        "hsv" => hsv_to_srgb(v[0], v[1] / 100.0, v[2] / 100.0),
+        "hwb" => hwb_to_srgb(v[0], v[1] / 100.0, v[2] / 100.0),
+        "lab" => lab_to_srgb(v[0], v[1], v[2]),
+        "lch" => {
+            let (a, b) = polar_to_axes(v[1], v[2]);
+            lab_to_srgb(v[0], a, b)
+        }
+        "oklab" => oklab_to_srgb(v[0], v[1], v[2]),
+        _ => {
+            let (a, b) = polar_to_axes(v[1], v[2]);
+            oklab_to_srgb(v[0], a, b)
+        }
+    };
+    let (r, g, b, a) = (to_u8(r), to_u8(g), to_u8(b), to_u8(alpha));
+    if a == 255 {
+        Ok(Color::Rgb(r, g, b))
+    } else {
+        Ok(Color::Hex(format!("#{r:02X}{g:02X}{b:02X}{a:02X}")))
+    }
+}
+
+/// Converts a gamma encoded sRGB component in the range `[0, 1]` to a byte.
+fn to_u8(c: f64) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Converts a chroma and a hue in degrees to the a and b axes of Lab.
+fn polar_to_axes(chroma: f64, hue: f64) -> (f64, f64) {
+    let hue = hue.to_radians();
+    (chroma * hue.cos(), chroma * hue.sin())
+}
+
+/// Converts a hue in degrees and a chroma in the range `[0, 1]` to the
+/// components of the fully saturated color with that hue, before the
+/// lightness is added.
+fn hue_to_srgb(hue: f64, chroma: f64) -> (f64, f64, f64) {
+    let h = hue / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    }
+}
+
+fn hsl_to_srgb(hue: f64, sat: f64, light: f64) -> (f64, f64, f64) {
+    let chroma = (1.0 - (2.0 * light - 1.0).abs()) * sat;
+    let (r, g, b) = hue_to_srgb(hue, chroma);
+    let m = light - chroma / 2.0;
+    (r + m, g + m, b + m)
+}
+
+fn hsv_to_srgb(hue: f64, sat: f64, value: f64) -> (f64, f64, f64) {
+    let chroma = value * sat;
+    let (r, g, b) = hue_to_srgb(hue, chroma);
+    let m = value - chroma;
+    (r + m, g + m, b + m)
+}
+
+fn hwb_to_srgb(hue: f64, white: f64, black: f64) -> (f64, f64, f64) {
+    if white + black >= 1.0 {
+        let gray = white / (white + black);
+        return (gray, gray, gray);
+    }
+    let (r, g, b) = hsl_to_srgb(hue, 1.0, 0.5);
+    let scale = 1.0 - white - black;
+    (r * scale + white, g * scale + white, b * scale + white)
+}
+
+/// Converts CIE Lab, relative to the D50 white point as in CSS, to sRGB.
+fn lab_to_srgb(light: f64, a: f64, b: f64) -> (f64, f64, f64) {
+    const EPSILON: f64 = 216.0 / 24389.0;
+    const KAPPA: f64 = 24389.0 / 27.0;
+    const D50: [f64; 3] =
+        [0.3457 / 0.3585, 1.0, (1.0 - 0.3457 - 0.3585) / 0.3585];
+
+    let fy = (light + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+    let f_inv = |f: f64| {
+        if f.powi(3) > EPSILON {
+            f.powi(3)
+        } else {
+            (116.0 * f - 16.0) / KAPPA
+        }
+    };
+    let y = if light > KAPPA * EPSILON { fy.powi(3) } else { light / KAPPA };
+    let xyz = [f_inv(fx) * D50[0], y * D50[1], f_inv(fz) * D50[2]];
+    // Adapt from D50 to D65 with the Bradford transform, then convert to
+    // linear sRGB.
+    let xyz = mul(
+        &[
+            [0.955473421488075, -0.02309845494876471, 0.06325924320057072],
+            [-0.0283697093338637, 1.0099953980813041, 0.021041441191917323],
+            [0.012314014864481998, -0.020507649298898964, 1.330365926242124],
+        ],
+        xyz,
+    );
+    let rgb = mul(
+        &[
+            [3.2409699419045226, -1.537383177570094, -0.4986107602930034],
+            [-0.9692436362808796, 1.8759675015077202, 0.04155505740717559],
+            [0.05563007969699366, -0.20397695888897652, 1.0569715142428786],
+        ],
+        xyz,
+    );
+    if in_gamut(rgb) {
+        return encode(rgb);
+    }
+    let [l, a, b] = linear_srgb_to_oklab(rgb);
+    oklab_to_srgb(l, a, b)
+}
+
+/// Converts Oklab to sRGB, reducing the chroma of colors that are outside
+/// of the sRGB gamut until they fit.
+fn oklab_to_srgb(light: f64, a: f64, b: f64) -> (f64, f64, f64) {
+    if light >= 1.0 {
+        return (1.0, 1.0, 1.0);
+    }
+    if light <= 0.0 {
+        return (0.0, 0.0, 0.0);
+    }
+    let rgb = oklab_to_linear_srgb([light, a, b]);
+    if in_gamut(rgb) {
+        return encode(rgb);
+    }
+    // Binary search for the largest chroma with the same hue that fits.
+    let (mut lo, mut hi) = (0.0, 1.0);
+    for _ in 0..24 {
+        let mid = (lo + hi) / 2.0;
+        if in_gamut(oklab_to_linear_srgb([light, a * mid, b * mid])) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    encode(oklab_to_linear_srgb([light, a * lo, b * lo]))
+}
+
+fn oklab_to_linear_srgb(lab: [f64; 3]) -> [f64; 3] {
+    let lms = mul(
+        &[
+            [1.0, 0.3963377774, 0.2158037573],
+            [1.0, -0.1055613458, -0.0638541728],
+            [1.0, -0.0894841775, -1.2914855480],
+        ],
+        lab,
+    );
+    mul(
+        &[
+            [4.0767416621, -3.3077115913, 0.2309699292],
+            [-1.2684380046, 2.6097574011, -0.3413193965],
+            [-0.0041960863, -0.7034186147, 1.7076147010],
+        ],
+        lms.map(|c| c.powi(3)),
+    )
+}
+
+fn linear_srgb_to_oklab(rgb: [f64; 3]) -> [f64; 3] {
+    let lms = mul(
+        &[
+            [0.4122214708, 0.5363288542, 0.0514459929],
+            [0.2119034982, 0.6806995451, 0.1073969566],
+            [0.0883024619, 0.2817188376, 0.6299787005],
+        ],
+        rgb,
+    );
+    mul(
+        &[
+            [0.2104542553, 0.7936177850, -0.0040720468],
+            [1.9779984951, -2.4285922050, 0.4505937099],
+            [0.0259040371, 0.7827717662, -0.8086757660],
+        ],
+        lms.map(f64::cbrt),
+    )
+}
+
+fn mul(m: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+/// Returns true if the given linear sRGB color is displayable, allowing for
+/// rounding errors.
+fn in_gamut(rgb: [f64; 3]) -> bool {
+    rgb.iter().all(|&c| (-1e-6..=1.0 + 1e-6).contains(&c))
+}
+
+/// Applies the sRGB transfer function to a linear sRGB color.
+fn encode(rgb: [f64; 3]) -> (f64, f64, f64) {
+    let f = |c: f64| {
+        if c <= 0.0031308 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    };
+    (f(rgb[0]), f(rgb[1]), f(rgb[2]))
 }
 
 /// A more flexible parser that can handle "ansi256" or "rgb".
@@ -162,3 +618,39 @@ pub fn parse_other(s: &str) -> Result<Color, ParseColorError> {
         })
     }
 }
+
+/// Splits a textual color specification (e.g., "fg:red bg:rgb(0, 0, 0) bold") into its tokens.
+///
+/// Tokens are separated by whitespace, except for whitespace inside of parentheses, so that colors
+/// such as "rgb(0, 0, 0)" remain a single token.
+///
+/// # Parameters:
+/// - `s`: A string slice containing the color specification.
+///
+/// # Returns:
+/// The non-empty tokens of the specification, in order.
+pub fn split_spec_tokens(s: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut depth = 0usize;
+    let mut start = None;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            c if c.is_whitespace() && depth == 0 => {
+                if let Some(st) = start.take() {
+                    tokens.push(&s[st..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(st) = start {
+        tokens.push(&s[st..]);
+    }
+    tokens
+}